@@ -0,0 +1,70 @@
+/*
+ * File   : benches/global.rs
+ * Purpose: benchmark global commands on large files
+ * Program: red
+ * About  : command-line text editor
+ * Authors: Tommy Lincoln <pajamapants3000@gmail.com>
+ * License: MIT; See LICENSE!
+ * Notes  : run with `cargo bench`; uses std timing, works on stable
+ * Created: 10/17/2026
+ */
+
+//! Times `g/re/` and `v/re/` through an Editor on a 1M-line file
+// *** Bring in to namespace *** {{{
+extern crate red;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
+
+use red::{Editor, MemoryTerminal};
+// ^^^ Bring in to namespace ^^^ }}}
+
+// *** Constants *** {{{
+const NUM_LINES: usize = 1_000_000;
+// ^^^ Constants ^^^ }}}
+
+// *** Functions *** {{{
+/// Print timing of one benchmark// {{{
+fn report( name: &str, count: usize, elapsed: Duration ) {// {{{
+    let nanos = elapsed.as_secs() as f64 * 1e9 +
+        elapsed.subsec_nanos() as f64;
+    println!( "{:<40} {:>10} lines {:>12.1} ns/line", name, count,
+              nanos / count as f64 );
+}// }}}
+// }}}
+fn main() {// {{{
+    let mut file_path = env::temp_dir();
+    file_path.push( "red_bench_global.txt" );
+    {
+        let mut writer = BufWriter::new( File::create( &file_path )
+                                         .expect( "unable to create file" ));
+        for indx in 0 .. NUM_LINES {
+            writeln!( writer, "line {} of the benchmark file", indx )
+                .expect( "unable to write file" );
+        }
+    }
+    let mut editor = Editor::open( file_path.to_str()
+                                   .expect( "temp dir is not UTF-8" ))
+        .expect( "unable to open file" );
+    let terminal = MemoryTerminal::default();
+    editor.set_terminal( Box::new( terminal.clone() ));
+
+    // every line is checked, one matches
+    let start = Instant::now();
+    editor.execute( ",g/^line 500000 of/p" ).expect( "g failed" );
+    report( "g/re/p, one match", NUM_LINES, start.elapsed() );
+    assert_eq!( terminal.take_output(),
+                "line 500000 of the benchmark file\n" );
+
+    // every line is checked, none is changed
+    let start = Instant::now();
+    editor.execute( ",v/benchmark/d" ).expect( "v failed" );
+    report( "v/re/d, no match", NUM_LINES, start.elapsed() );
+    assert_eq!( editor.num_lines(), NUM_LINES );
+
+    editor.close();
+    let _ = fs::remove_file( &file_path );
+}// }}}
+// ^^^ Functions ^^^ }}}
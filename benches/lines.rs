@@ -0,0 +1,124 @@
+/*
+ * File   : benches/lines.rs
+ * Purpose: benchmark line storage on large files
+ * Program: red
+ * About  : command-line text editor
+ * Authors: Tommy Lincoln <pajamapants3000@gmail.com>
+ * License: MIT; See LICENSE!
+ * Notes  : run with `cargo bench --features bench`; uses std timing
+ * Created: 10/17/2026
+ */
+
+//! Times the operations Buffer relies on against a 1M-line file
+// *** Bring in to namespace *** {{{
+extern crate red;
+
+use std::collections::LinkedList;
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::iter::FromIterator;
+use std::time::{Duration, Instant};
+
+use red::LineStore;
+// ^^^ Bring in to namespace ^^^ }}}
+
+// *** Constants *** {{{
+const NUM_LINES: usize = 1_000_000;
+const NUM_ACCESSES: usize = 100_000;
+/// LinkedList lookups are O(n); keep the comparison short
+const NUM_LIST_ACCESSES: usize = 200;
+// ^^^ Constants ^^^ }}}
+
+// *** Functions *** {{{
+/// Deterministic pseudo-random index sequence// {{{
+fn next_index( seed: &mut u64, len: usize ) -> usize {// {{{
+    *seed = seed.wrapping_mul( 6364136223846793005 ).wrapping_add( 1 );
+    ( ( *seed >> 33 ) as usize ) % len
+}// }}}
+// }}}
+/// Print timing of one benchmark// {{{
+fn report( name: &str, count: usize, elapsed: Duration ) {// {{{
+    let nanos = elapsed.as_secs() as f64 * 1e9 +
+        elapsed.subsec_nanos() as f64;
+    println!( "{:<40} {:>10} ops {:>14.1} ns/op", name, count,
+              nanos / count as f64 );
+}// }}}
+// }}}
+fn main() {// {{{
+    let mut file_path = env::temp_dir();
+    file_path.push( "red_bench_1m_lines.txt" );
+    {
+        let mut writer = BufWriter::new( File::create( &file_path )
+                                         .expect( "unable to create file" ));
+        for indx in 0 .. NUM_LINES {
+            writeln!( writer, "line {} of the benchmark file", indx )
+                .expect( "unable to write file" );
+        }
+    }
+
+    let start = Instant::now();
+    let reader = BufReader::new( File::open( &file_path )
+                                 .expect( "unable to open file" ));
+    let mut store = LineStore::from_iter( reader.lines()
+                                          .map( |x| x.unwrap() ));
+    report( "load 1M-line file", 1, start.elapsed() );
+
+    let mut seed: u64 = 1;
+    let start = Instant::now();
+    let mut total: usize = 0;
+    for _ in 0 .. NUM_ACCESSES {
+        let indx = next_index( &mut seed, store.len() );
+        total += store.get( indx ).map( |x| x.len() ).unwrap_or( 0 );
+    }
+    report( "LineStore random get", NUM_ACCESSES, start.elapsed() );
+
+    let start = Instant::now();
+    for indx in 0 .. store.len() {
+        total += store.get( indx ).map( |x| x.len() ).unwrap_or( 0 );
+    }
+    report( "LineStore sequential get (g/re/ scan)", store.len(),
+            start.elapsed() );
+
+    let start = Instant::now();
+    for indx in 0 .. NUM_ACCESSES {
+        store.insert( 500_000 + indx, "inserted line".to_string() );
+    }
+    report( "LineStore insert, localized", NUM_ACCESSES, start.elapsed() );
+
+    let start = Instant::now();
+    for _ in 0 .. NUM_ACCESSES {
+        store.remove( 500_000 );
+    }
+    report( "LineStore remove, localized", NUM_ACCESSES, start.elapsed() );
+
+    let num_scattered = NUM_ACCESSES / 100;
+    let start = Instant::now();
+    for _ in 0 .. num_scattered {
+        let indx = next_index( &mut seed, store.len() );
+        store.insert( indx, "inserted line".to_string() );
+        let indx = next_index( &mut seed, store.len() );
+        store.remove( indx );
+    }
+    report( "LineStore insert+remove, scattered", num_scattered,
+            start.elapsed() );
+
+    let start = Instant::now();
+    for line in store.iter() {
+        total += line.len();
+    }
+    report( "LineStore full iteration", store.len(), start.elapsed() );
+
+    let list: LinkedList<String> = store.iter().cloned().collect();
+    let start = Instant::now();
+    for _ in 0 .. NUM_LIST_ACCESSES {
+        let indx = next_index( &mut seed, list.len() );
+        total += list.iter().nth( indx ).map( |x| x.len() ).unwrap_or( 0 );
+    }
+    report( "LinkedList random get (previous storage)", NUM_LIST_ACCESSES,
+            start.elapsed() );
+
+    println!( "(checksum {})", total );
+    let _ = fs::remove_file( &file_path );
+}// }}}
+// ^^^ Functions ^^^ }}}
@@ -11,14 +11,15 @@
 
 // Use LineWriter instead of, or in addition to, BufWriter?
 use std::io::prelude::*;
-use std::io::{BufReader, BufWriter, stdout};
+use std::io::{self, BufWriter};
 use std::fs::{self, File, rename};
 use std::path::{Path, PathBuf};
-use std::collections::LinkedList;
-use std::collections::linked_list::{self, Iter};
-use std::iter::{IntoIterator, FromIterator, Iterator, Take, Skip};
-use std::{thread, time};
+use std::iter::{FromIterator, Iterator};
+use std::{env, thread, time};
 use std::ffi::{OsStr,OsString};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::time::SystemTime;
 
 use ::chrono::*;
 use ::regex::{Regex, Captures};
@@ -27,6 +28,9 @@ use ::rand::{thread_rng, Rng};
 use io::*;
 use error::*;
 use parse::*;
+use lines::{LineStore, Lines};
+use journal::{self, Journal, FlushPolicy};
+use encoding::{decode_text, encode, TextFormat};
 
 // ^^^ Bring in to namespace ^^^ }}}
 // *** Attributes *** {{{
@@ -35,6 +39,10 @@ use parse::*;
 // *** Constants *** {{{
 pub const NUM_LC: usize = 26;
 const SAVE_RETRIES: usize = 3;
+/// start of name of temp file a write goes to before replacing the file
+const WRITE_FILE_PREFIX: &'static str = ".~";
+/// most symbolic links followed to find file to write
+const MAX_LINKS: usize = 40;
 // ^^^ Constants ^^^ }}}
 
 // *** Data Structures *** {{{
@@ -46,20 +54,57 @@ const SAVE_RETRIES: usize = 3;
 pub enum BufferInput {// {{{
     File(String),         // box it?
     Command(String),    // OsString? box it?
+    Text(String),
     None,
 }// }}}
 // }}}
+/// What a file looked like, to tell whether it changed since// {{{
+#[derive(Clone, Debug)]
+pub struct FileStamp {// {{{
+    modified: SystemTime,
+    size: u64,
+    hash: u64,
+}// }}}
+// }}}
+impl FileStamp {// {{{
+    /// Stamp file last modified at given time, with given content// {{{
+    fn new( modified: SystemTime, content: &[u8] ) -> FileStamp {// {{{
+        let mut hasher = DefaultHasher::new();
+        hasher.write( content );
+        FileStamp{ modified: modified, size: content.len() as u64,
+                   hash: hasher.finish() }
+    }// }}}
+    // }}}
+    /// Return true if both stamps are of the same content// {{{
+    ///
+    /// Modification time only saves reading a file that wasn't touched
+    fn same_content( &self, other: &FileStamp ) -> bool {// {{{
+        self.size == other.size && self.hash == other.hash
+    }// }}}
+    // }}}
+}// }}}
+/// File last read or written, and its stamp then// {{{
+///
+/// Stamp is None if file didn't exist
+#[derive(Clone, Debug)]
+struct DiskState {// {{{
+    path: OsString,
+    stamp: Option<FileStamp>,
+}// }}}
+// }}}
 /// Stores collection of lines containing current working text// {{{
 ///
 pub struct Buffer {     //{{{
-    /// the current working buffer content as a list of lines
-    lines: LinkedList<String>,
+    /// the current working buffer content as an indexed store of lines
+    lines: LineStore,
     /// the optional path of file being worked on
     ///
     /// Is None if no exising file was loaded and not yet saved
     file: Option<OsString>,
     /// timestamped path of file where buffer is stored regularly
     buffer_file: OsString,  // convert to Path later
+    /// changes made since buffer_file was last compacted
+    journal: Journal,
     /// collection of markers set for lines in lines
     markers: Vec<usize>,
     /// line number of "cursor"
@@ -70,18 +115,25 @@ pub struct Buffer {     //{{{
     total_lines: usize,
     /// true if file has been modified since last write
     _is_modified: bool,
-    /// Date and time of last read of source file
-    //last_update: DateTime<UTC>,
+    /// File as found on disk when last read or written
+    ///
+    /// Is None if no file was read or written yet
+    last_update: Option<DiskState>,
     /// Date and time of last write to disk under temporary file name
     last_temp_write: DateTime<UTC>,
     /// Date and time of last write to disk under permanent file name
     last_write: DateTime<UTC>,
+    /// true if shell commands and paths outside current directory refused
+    restricted: bool,
+    /// line endings to write, as found in file loaded
+    format: TextFormat,
 }   //}}}
 // }}}
 impl Buffer {   //{{{
     /// Initialize new Buffer instance// {{{
     pub fn new( content: BufferInput ) -> Result<Buffer, RedError> {//{{{
-        let mut _lines = Buffer::init_lines( &content );
+        let ( mut _lines, _format, _stamp ) = try!(
+                Buffer::init_lines( &content ));
         let _total_lines = _lines.len();
         let mut result = Buffer {
             lines: _lines,
@@ -90,28 +142,29 @@ impl Buffer {   //{{{
                     temp_file_name( Some( file_name.as_str() )),
                 _ => temp_file_name( None::<&str> ),
             },
+            journal: Journal::new(),
             markers: vec!( 0; NUM_LC ),
             current_line: _total_lines,     // usize; should be Copy
             total_lines: _total_lines,
             _is_modified: false,
-            /*  Comment until it becomes relevant
-            last_update: match &content {
-                &BufferInput::File(_) => UTC::now(),
-                _ => get_null_time(),
-            },
-            */
+            last_update: None,
             last_temp_write: match &content {
                 &BufferInput::File(_) => UTC::now(),
                 _ => get_null_time(),
             },
             last_write: get_null_time(),
             file: None,
+            restricted: false,
+            format: _format,
         };
 
         match content {
             BufferInput::File( file_name ) =>
                 match result.set_file( &file_name ) {
-                    Ok( () ) => {},
+                    Ok( () ) => {
+                        result.last_update = result.file.clone().map( |path|
+                                DiskState{ path: path, stamp: _stamp } );
+                    },
                     Err(_) => {},
                 },
             BufferInput::Command( command ) =>
@@ -122,40 +175,18 @@ impl Buffer {   //{{{
                 },
             _ => {},
         };
-        let half_second: time::Duration = time::Duration::from_millis(500);
+        let retry_delay: time::Duration = time::Duration::from_secs(2);
         for attempt in 1 .. ( SAVE_RETRIES + 1 ) {
             match result.store_buffer() {
                 Ok(_) => {
                     return Ok( result );
                 },
                 Err(e) => {
-                    println!( "Attempt {}/{}: unable to store buffer",
-                            attempt, SAVE_RETRIES );
-                    let _stdout = stdout();
-                    let mut handle = _stdout.lock();
-                    thread::sleep(half_second);
-                    handle.write( b"." )
-                        .expect("buf::Buffer::new: fail to write to stdout");
-                    handle.flush()
-                        .expect("buf::Buffer::new: fail to write to stdout");
-                    thread::sleep(half_second);
-                    handle.write( b"." )
-                        .expect("buf::Buffer::new: fail to write to stdout");
-                    handle.flush()
-                        .expect("buf::Buffer::new: fail to write to stdout");
-                    thread::sleep(half_second);
-                    handle.write( b"." )
-                        .expect("buf::Buffer::new: fail to write to stdout");
-                    handle.flush()
-                        .expect("buf::Buffer::new: fail to write to stdout");
-                    thread::sleep(half_second);
-                    handle.write( b".\n" )
-                        .expect("buf::Buffer::new: fail to write to stdout");
-                    handle.flush()
-                        .expect("buf::Buffer::new: fail to write to stdout");
+                    // failure is returned to caller once retries run out
                     if attempt == SAVE_RETRIES {
                         return Err( e );
                     }
+                    thread::sleep( retry_delay );
                 },
             }
         }
@@ -172,11 +203,105 @@ impl Buffer {   //{{{
     pub fn is_modified( &self ) -> bool {// {{{
         self._is_modified
     }// }}}
+// }}}
+    /// Return true if file changed on disk since last read or written// {{{
+    ///
+    /// Empty file_name means the current file; any other file counts as
+    /// unchanged unless it is the current file under another name. Only
+    /// if size or modification time differ is the content compared, so
+    /// a file touched, or changed and changed back, is unchanged
+    pub fn changed_on_disk( &self, file_name: &str ) -> bool {// {{{
+        if !self.is_current_file( file_name ) {
+            return false;
+        }
+        match self.last_update {
+            // stamp is of another file if renamed since
+            Some( ref last ) if self.is_current_file( &last.path ) =>
+                    match file_stamp( &last.path,
+                                                  last.stamp.as_ref() ) {
+                Ok( stamp ) => match ( stamp, last.stamp.as_ref() ) {
+                    ( Some( now ), Some( then )) => !now.same_content( then ),
+                    ( None, None ) => false,
+                    _ => true,
+                },
+                // can't tell, so don't let it be overwritten unawares
+                Err(_) => true,
+            },
+            _ => false,
+        }
+    }// }}}
+// }}}
+    /// Return true if file_name is empty or names the current file// {{{
+    fn is_current_file<S: AsRef<OsStr> + ?Sized>( &self, file_name: &S )// {{{
+            -> bool {
+        if file_name.as_ref().is_empty() {
+            return true;
+        }
+        let current = match self.file {
+            Some( ref f ) => Path::new( f ),
+            None => return false,
+        };
+        let path = Path::new( file_name );
+        match ( path.canonicalize(), current.canonicalize() ) {
+            ( Ok( p ), Ok( c ) ) => p == c,
+            _ => path == current,
+        }
+    }// }}}
+// }}}
+    /// Return line endings used when writing// {{{
+    pub fn get_format( &self ) -> TextFormat {// {{{
+        self.format
+    }// }}}
+// }}}
+    /// Change line endings used when writing// {{{
+    ///
+    /// A change counts as a modification, so it isn't lost on quitting
+    pub fn set_format( &mut self, format: TextFormat ) {// {{{
+        if format != self.format {
+            self.format = format;
+            self._is_modified = true;
+        }
+    }// }}}
+// }}}
+    /// Refuse shell commands and paths outside current directory// {{{
+    ///
+    /// There is no way back; a buffer replacing this one should be
+    /// restricted too
+    pub fn restrict( &mut self ) {// {{{
+        self.restricted = true;
+    }// }}}
+// }}}
+    /// Return true if buffer is restricted// {{{
+    pub fn is_restricted( &self ) -> bool {// {{{
+        self.restricted
+    }// }}}
+// }}}
+    /// Return error if buffer is restricted and content is not allowed// {{{
+    ///
+    /// Command output is never allowed, files only in current directory
+    pub fn check_input( &self, content: &BufferInput )// {{{
+            -> Result<(), RedError> {
+        if !self.restricted {
+            return Ok( () );
+        }
+        match *content {
+            BufferInput::Command( ref command ) =>
+                Err( RedError::Restricted{ parameter: command.clone() } ),
+            BufferInput::File( ref file_name ) =>
+                check_restricted_path( file_name ),
+            _ => Ok( () ),
+        }
+    }// }}}
 // }}}
     // later, change approach to homogenize file/stdout source
     // generate iterator over BufRead object, either file, stdout, or empty
-    /// Return the linked-list of lines to store in buffer// {{{
-    fn init_lines( content: &BufferInput ) -> LinkedList<String> {// {{{
+    /// Return the store of lines to hold in buffer, their format, and// {{{
+    /// the stamp of file they were read from, if it exists
+    ///
+    /// A file that can't be read gives an empty buffer, but a command that
+    /// can't be run is an error
+    fn init_lines( content: &BufferInput )// {{{
+            -> Result<( LineStore, TextFormat, Option<FileStamp> ), RedError> {
         match *content {
             BufferInput::File( ref file_name ) => {
                 let file_path = Path::new( &file_name );
@@ -185,7 +310,7 @@ impl Buffer {   //{{{
                 if !_file.exists() {
                     return Buffer::init_lines( &BufferInput::None );
                 }
-                let file_opened: File;
+                let mut file_opened: File;
                 match file_opener( file_name, file_mode ) {
                     Ok( _file_opened ) => {
                         file_opened = _file_opened;
@@ -194,72 +319,61 @@ impl Buffer {   //{{{
                         return Buffer::init_lines( &BufferInput::None );
                     },
                 }
-                let reader = BufReader::new( file_opened );
-                LinkedList::from_iter( reader.lines()
-                                             .map(|result| result.unwrap() ) )
-                // reader.lines() returns an iterator (Lines type) over
-                // io::Result<String>
-                // We map that to the String (at least that's my intention!)
-                // it seems like this happens on the iterator level, never
-                // needing to do the iteration - should be efficient
-                // (again... that's my intention!)
+                let mut content: Vec<u8> = Vec::new();
+                let modified = match file_opened.read_to_end( &mut content )
+                        .and_then( |_| file_opened.metadata() )
+                        .and_then( |metadata| metadata.modified() ) {
+                    Ok( x ) => x,
+                    Err(_) => {
+                        return Buffer::init_lines( &BufferInput::None );
+                    },
+                };
+                let stamp = FileStamp::new( modified, &content );
+                // bytes that aren't UTF-8 are kept, to be written back as is
+                let ( lines, format ) = decode_text( &content );
+                Ok(( LineStore::from_iter( lines ), format, Some( stamp )))
             },
             BufferInput::Command(ref command) => {
-                LinkedList::from_iter( command_output( command ).lines()
-                                         .map(|x| x.to_string() ) )
+                let output = try!( command_output( command ));
+                Ok(( LineStore::from_iter( output.lines()
+                                         .map(|x| x.to_string() ) ),
+                     TextFormat::default(), None ))
+            },
+            BufferInput::Text(ref text) => {
+                Ok(( LineStore::from_iter( text.lines()
+                                         .map(|x| x.to_string() ) ),
+                     TextFormat::default(), None ))
             },
             BufferInput::None => {
-                LinkedList::from_iter( "".to_string().lines()
-                                         .map(|x| x.to_string() ) )
+                Ok(( LineStore::new(), TextFormat::default(), None ))
             },
         }
     }// }}}
 // }}}
     /// Return single line// {{{
     ///
-    /// TODO: This is a terrible approach - you have direct
-    /// access to the private members, don't waste a function
-    /// call! Plus the iteration... should be able to find a
-    /// better way.
+    /// Line 0 is treated as an empty line before the first line
     pub fn get_line_content( &self, line: usize ) -> Option<&str> {// {{{
-        let mut lines_iter = self.lines_iterator();
-        let mut _line: usize = 1;
-        let mut result: &str = "";
-        while _line <= line {
-            match lines_iter.next() {
-                Some( content ) => {
-                    if _line == line {
-                        result = content;
-                    }
-                },
-                None => {
-                    return None;
-                },
-            }
-            _line += 1;
+        if line == 0 {
+            return Some( "" );
         }
-        Some(result)
+        self.lines.get( line - 1 ).map( |x| x.as_str() )
     }// }}}
 // }}}
     /// Return iterator over range of lines in buffer// {{{
     ///
     pub fn range_iterator<'a>( &'a self, address_initial: usize,// {{{
-                           address_final: usize ) -> Take<
-            Skip<linked_list::Iter<'a, String> >> {
+                           address_final: usize ) -> Lines<'a> {
         // Make sure caller did their job!
         assert_addresses( address_initial, address_final, self.total_lines );
         // now let's do ours...
-        let lines_ref: &'a LinkedList<String> = &self.lines;
-        lines_ref.into_iter()
-            .skip( address_initial - 1 )
-            .take(( address_final + 1 ) - address_initial )
+        self.lines.range( address_initial - 1, address_final )
     }// }}}
 // }}}
     /// Return iterator over all lines in buffer// {{{
     ///
-    pub fn lines_iterator( &self ) -> Iter<String> {
-        let lines_ref: &LinkedList<String> = &self.lines;
-        lines_ref.into_iter()
+    pub fn lines_iterator<'a>( &'a self ) -> Lines<'a> {
+        self.lines.iter()
     }// }}}
 // }}}
     /// Return reference to working file name string// {{{
@@ -275,8 +389,7 @@ impl Buffer {   //{{{
         }
     }// }}}
 // }}}
-    /// Return reference to working file name string// {{{
-    #[cfg(test)]
+    /// Return reference to full working file path// {{{
     pub fn get_file_path( &self ) -> Option<&OsStr> {// {{{
         match &self.file {
             &Some( ref file_path ) => Some( file_path ),
@@ -302,6 +415,9 @@ impl Buffer {   //{{{
     /// At some point, need to test for existing file and ask user if overwrite
     pub fn set_file<S: AsRef<OsStr> + ?Sized>( &mut self, path: &S )
             -> Result<(), RedError> {// {{{
+        if self.restricted {
+            try!( check_restricted_path( path ));
+        }
         let result: Option<OsString>;
         let file_path = Path::new( path );
         // abort if no valid file name provided
@@ -340,18 +456,11 @@ impl Buffer {   //{{{
     ///     would be line doesn't exist, in which case we can just do nothing.
     pub fn delete_line( &mut self, address: usize )
             -> Result<(), RedError> {// {{{
-        if address > self.lines.len() {
+        if address > self.lines.len() || address == 0 {
             return Err( RedError::GetLineOutOfBounds{ address: address } );
         }
-        let mut back = self.lines.split_off( address );
-        match self.lines.pop_back() {
-            Some(_) => {},
-            None => {
-                return Err(
-                    RedError::GetLineOutOfBounds{ address: address } );
-            },
-        }
-        self.lines.append( &mut back );
+        self.lines.remove( address - 1 );
+        self.journal.record_remove( address );
         self.delete_update_markers( address );
         self.total_lines -= 1; // previous tests preclude underflow here?
         self._is_modified = true;
@@ -372,14 +481,58 @@ impl Buffer {   //{{{
     /// TODO: Add error handling, Result<> return?
     ///     I don't think that will be necessary
     pub fn append_line( &mut self, address: usize, new_line: &str ) {// {{{
-        let mut back = self.lines.split_off( address );
-        self.lines.push_back( new_line.to_string() );
-        self.lines.append( &mut back );
+        self.lines.insert( address, new_line.to_string() );
+        self.journal.record_add( address + 1, new_line );
         self.current_line = address + 1;   // next line
         self.insert_update_markers( address );
         self._is_modified = true;
         self.total_lines += 1;
     }// }}}
+// }}}
+    /// Insert content of file or command output after address// {{{
+    ///
+    /// Unlike init_lines, a file that can't be opened is an error here;
+    /// Returns number of lines and number of bytes read, and whether a
+    /// newline had to be appended to the last line read.
+    /// Lines read take on the line endings of this buffer
+    pub fn read_lines( &mut self, address: usize, content: &BufferInput )// {{{
+            -> Result<(usize, usize, bool), RedError> {
+        let mut newline_appended = false;
+        let mut file_bytes: Option<usize> = None;
+        let new_lines: Vec<String> = match *content {
+            BufferInput::File( ref file_name ) => {
+                let file_mode = FileMode{ f_read: true, ..Default::default() };
+                let mut file_opened = try!( file_opener( file_name,
+                                                         file_mode ));
+                let mut content: Vec<u8> = Vec::new();
+                try!( file_opened.read_to_end( &mut content )
+                      .map_err( |e| RedError::FileRead(e) ));
+                let ( lines, format ) = decode_text( &content );
+                newline_appended = !format.final_newline;
+                file_bytes = Some( content.len() );
+                lines
+            },
+            BufferInput::Command( ref command ) => {
+                try!( command_output( command )).lines()
+                    .map( |x| x.to_string() ).collect()
+            },
+            BufferInput::Text( ref text ) =>
+                text.lines().map( |x| x.to_string() ).collect(),
+            BufferInput::None => Vec::new(),
+        };
+        let mut num_bytes: usize = 0;
+        let mut _address = address;
+        for line in &new_lines {
+            self.append_line( _address, line );
+            num_bytes += encode( line ).len() + 1;
+            _address += 1;
+        }
+        if new_lines.is_empty() {
+            self.current_line = address;
+        }
+        Ok( ( new_lines.len(), file_bytes.unwrap_or( num_bytes ),
+              newline_appended ) )
+    }// }}}
 // }}}
     /// Replace line with new string// {{{
     ///
@@ -387,13 +540,12 @@ impl Buffer {   //{{{
     ///     I don't think that will be necessary
     pub fn set_line_content( &mut self, address: usize, new_line: &str )// {{{
             -> Result<(), RedError> {
-        if address > self.lines.len() {
+        if address > self.lines.len() || address == 0 {
             return Err( RedError::SetLineOutOfBounds{ address: address } );
         }
-        let mut back_list = self.lines.split_off( address - 1 );
-        let _ = back_list.pop_front();
-        self.lines.push_back( new_line.to_string() );
-        self.lines.append( &mut back_list );
+        self.lines.set( address - 1, new_line.to_string() );
+        self.journal.record_remove( address );
+        self.journal.record_add( address, new_line );
         self._is_modified = true;
         Ok( () )
     }// }}}
@@ -427,44 +579,35 @@ impl Buffer {   //{{{
         self.markers[ (( label as u8 ) - ( 'a' as u8 )) as usize ] = line;
     }// }}}
 // }}}
-    /// Return immutable slice over all markers// {{{
+    /// Return label and line of each marker that is set// {{{
     ///
     /// Will add operation for this at some point; until then, commented out
     /*
-    pub fn list_markers( &self ) {// {{{
+    pub fn list_markers( &self ) -> Vec<( char, usize )> {// {{{
+        let mut result = Vec::new();
         let mut indx: u8 = 0;
         for marker in &self.markers {
             if *marker != 0 {
-                println!("{}: {}", (( 'a' as u8 ) + indx) as char, marker );
+                result.push( ( ( ( 'a' as u8 ) + indx ) as char, *marker ));
             }
             indx += 1;
         }
+        result
     }// }}}
 // }}}
     */
     /// Write buffer contents to temp file// {{{
     ///
+    /// Writes a fresh journal snapshot, compacting away any change records
     /// TODO: Delete on buffer destruct or at least on program exit
     pub fn store_buffer( &mut self ) -> Result<(), RedError> {// {{{
         let file_mode = FileMode { f_write: true, f_create: true,
-                ..Default::default() };
+                f_truncate: true, ..Default::default() };
         let temp_file_opened = try!( file_opener(
                 &self.buffer_file, file_mode ) );
         let mut writer = BufWriter::new( temp_file_opened );
-        {
-            let mut _lines_iterator = self.lines_iterator();
-            loop {
-                match _lines_iterator.next() {
-                    Some(x) => {
-                        try!( writer.write( x.as_bytes() )
-                                .map_err( |e| RedError::FileWrite(e) ) );
-                    },
-                    None => break,
-                }
-                try!( writer.write( b"\n" )
-                      .map_err( |e| RedError::FileWrite(e) ));
-            }
-        }
+        try!( journal::write_snapshot( &mut writer, self.total_lines,
+                                       self.lines_iterator() ));
         try!( writer.flush().map_err( |e| RedError::FileWrite(e) ));
         let new_buffer_file = match &self.file {
             &Some(ref x) => temp_file_name( Some(x) ),
@@ -475,8 +618,30 @@ impl Buffer {   //{{{
             );
         self.buffer_file = new_buffer_file;
         self.last_temp_write = UTC::now();
+        self.journal.snapshot_written();
         Ok( () )
     }// }}}
+// }}}
+    /// Write journal records to temp file if flush policy calls for it// {{{
+    pub fn sync_journal( &mut self, policy: FlushPolicy )// {{{
+            -> Result<(), RedError> {
+        if self.journal.is_due( policy ) {
+            self.flush_journal()
+        } else {
+            Ok( () )
+        }
+    }// }}}
+// }}}
+    /// Write journal records to temp file, compacting if worthwhile// {{{
+    pub fn flush_journal( &mut self ) -> Result<(), RedError> {// {{{
+        if self.journal.needs_compaction( self.total_lines ) {
+            self.store_buffer()
+        } else {
+            try!( self.journal.flush( &self.buffer_file ));
+            self.last_temp_write = UTC::now();
+            Ok( () )
+        }
+    }// }}}
 // }}}
     /// Save work to permanent file; behavior depends on do_append argument// {{{
     ///
@@ -493,8 +658,9 @@ impl Buffer {   //{{{
         // Make sure caller did their job!
         assert_addresses( address_initial, address_final, self.total_lines );
         // now let's do ours...
-        let file_mode = FileMode{ f_write: !do_append, f_truncate: !do_append,
-                f_append: do_append, f_create: true, ..Default::default() };
+        if self.restricted && !file_name.as_ref().is_empty() {
+            try!( check_restricted_path( file_name ));
+        }
         // set as default file if one provided but not previously set
         if !file_name.as_ref().is_empty() && self.file == None {
             self.file = Some( file_name.as_ref().to_os_string() );
@@ -507,21 +673,30 @@ impl Buffer {   //{{{
                         f
                     },
                     &None => {
-                        println!("No file name chosen for save");
                         return Err(
                             RedError::ParameterSyntax{
-                                parameter: "".to_string() });
+                                parameter: "no file name chosen for save"
+                                        .to_string() });
                     },
                 }
             },
             false => file_name.as_ref(),
         };
-        let mut file_opened = try!( file_opener( file_to_use, file_mode ));
-        for line in self.range_iterator( address_initial, address_final ) {
-            try!( file_opened.write( line.as_bytes() )
-                  .map_err(|e| RedError::FileWrite(e) ));
-            try!( file_opened.write( &[b'\n'] )
-                  .map_err(|e| RedError::FileWrite(e) ));
+        if do_append {
+            let file_mode = FileMode{ f_append: true, f_create: true,
+                    ..Default::default() };
+            let mut file_opened = try!( file_opener( file_to_use, file_mode ));
+            try!( self.write_lines( &mut file_opened, address_initial,
+                                    address_final ));
+        } else {
+            try!( self.replace_file( Path::new( file_to_use ), address_initial,
+                                     address_final ));
+        }
+        if self.is_current_file( file_name ) {
+            // what we wrote is the new baseline for changes by others
+            let path = self.file.clone().expect( "write_to_disk: no file" );
+            self.last_update = Some( DiskState{ path: path.clone(),
+                    stamp: file_stamp( &path, None ).unwrap_or( None ) });
         }
         if file_name.as_ref().is_empty() && address_initial == 1 &&
                 address_final == self.total_lines {
@@ -531,18 +706,98 @@ impl Buffer {   //{{{
         Ok( () )
 
     }// }}}
+// }}}
+    /// Write range of lines, with line endings as in file loaded// {{{
+    fn write_lines<W: Write>( &self, writer: &mut W,// {{{
+            address_initial: usize, address_final: usize )
+            -> Result<(), RedError> {
+        let line_ending = self.format.line_ending.as_bytes();
+        let mut address = address_initial;
+        for line in self.range_iterator( address_initial, address_final ) {
+            try!( writer.write_all( &encode( line ))
+                  .map_err(|e| RedError::FileWrite(e) ));
+            // last line lacks newline only if it did in file loaded
+            if address < self.total_lines || self.format.final_newline {
+                try!( writer.write_all( line_ending )
+                      .map_err(|e| RedError::FileWrite(e) ));
+            }
+            address += 1;
+        }
+        writer.flush().map_err(|e| RedError::FileWrite(e) )
+    }// }}}
+// }}}
+    /// Replace file with range of lines, all at once// {{{
+    ///
+    /// Lines go to a temp file beside the file, which is synced and given
+    /// the file's permissions before being renamed over it; a failure at
+    /// any point leaves the file as it was. A symbolic link is followed,
+    /// so it is the file linked to that is replaced
+    fn replace_file( &self, path: &Path, address_initial: usize,// {{{
+            address_final: usize ) -> Result<(), RedError> {
+        if self.restricted {
+            // file may have been linked elsewhere since it was named
+            try!( check_restricted_path( path ));
+        }
+        let target = try!( resolve_links( path )
+                           .map_err(|e| RedError::FileWrite(e) ));
+        let original = fs::metadata( &target ).ok();
+        if original.is_some() {
+            // renaming would get around a file we may not write to
+            try!( file_opener( &target, FileMode{ f_write: true,
+                    ..Default::default() } ));
+        }
+        let dir = match target.parent() {
+            Some( d ) if !d.as_os_str().is_empty() => d.to_path_buf(),
+            _ => PathBuf::from( "." ),
+        };
+        let temp_path = dir.join( format!( "{}{}.{}", WRITE_FILE_PREFIX,
+                target.file_name().unwrap_or( OsStr::new( "" ))
+                      .to_string_lossy(),
+                random_string() ));
+        let result = self.write_temp( &temp_path, original,
+                                      address_initial, address_final )
+            .and_then( |_| rename( &temp_path, &target )
+                       .map_err(|e| RedError::FileRename(e) ));
+        if result.is_err() {
+            let _ = fs::remove_file( &temp_path );
+        }
+        // make rename itself durable; not possible everywhere
+        if let Ok( dir_opened ) = File::open( &dir ) {
+            let _ = dir_opened.sync_all();
+        }
+        result
+    }// }}}
+// }}}
+    /// Write range of lines to new temp file and sync it to disk// {{{
+    ///
+    /// Temp file takes permissions of original file, if there is one
+    fn write_temp( &self, temp_path: &Path, original: Option<fs::Metadata>,// {{{
+            address_initial: usize, address_final: usize )
+            -> Result<(), RedError> {
+        let file_mode = FileMode{ f_write: true, f_create_new: true,
+                ..Default::default() };
+        let temp_opened = try!( file_opener( temp_path, file_mode ));
+        if let Some( metadata ) = original {
+            try!( temp_opened.set_permissions( metadata.permissions() )
+                  .map_err(|e| RedError::FileWrite(e) ));
+        }
+        let mut writer = BufWriter::new( temp_opened );
+        try!( self.write_lines( &mut writer, address_initial, address_final ));
+        writer.get_ref().sync_all().map_err(|e| RedError::FileWrite(e) )
+    }// }}}
 // }}}
     /// Pattern match predicate // {{{
     ///
-    /// Returns true if line has pattern match, false otherwise
-    pub fn does_match( &self, regex: &str, address: usize ) -> bool {// {{{
-        let re = Regex::new( regex ).unwrap();
+    /// Returns true if line has pattern match, false otherwise; regex is
+    /// compiled by caller, once for all the lines it checks
+    pub fn does_match( &self, re: &Regex, address: usize ) -> bool {// {{{
         re.is_match( self.get_line_content( address ).unwrap_or("") )
     }// }}}
 // }}}
     /// Return number of next matching line// {{{
-    pub fn find_match( &self, regex: &str ) -> Option<usize> {// {{{
-        let re = Regex::new( regex ).unwrap();
+    pub fn find_match( &self, regex: &str )// {{{
+            -> Result<Option<usize>, RedError> {
+        let re = try!( compile_regex( regex ));
         let mut lines_iter = self.lines_iterator();
         for _ in 1 .. self.current_line {
             lines_iter.next();              // start at current line
@@ -552,7 +807,7 @@ impl Buffer {   //{{{
             match lines_iter.next() {
                 Some( line ) => {
                     if re.is_match( line.as_str() ) {
-                        return Some( index );
+                        return Ok( Some( index ));
                     }
                 },
                 None => break,
@@ -565,19 +820,20 @@ impl Buffer {   //{{{
             match lines_iter.next() {
                 Some( line ) => {
                     if re.is_match( line.as_str() ) {
-                        return Some( index );
+                        return Ok( Some( index ));
                     }
                 },
-                None => return None,
+                None => return Ok( None ),
             }
             index += 1;
         }
-        None
+        Ok( None )
     }// }}}
 // }}}
     /// Return number of previous matching line// {{{
-    pub fn find_match_reverse( &self, regex: &str ) -> Option<usize> {// {{{
-        let re = Regex::new( regex ).unwrap();
+    pub fn find_match_reverse( &self, regex: &str )// {{{
+            -> Result<Option<usize>, RedError> {
+        let re = try!( compile_regex( regex ));
         let mut lines_iter = self.lines_iterator();
         for _ in self.current_line .. ( self.total_lines + 1 ) {
             lines_iter.next_back();              // start at current line
@@ -587,7 +843,7 @@ impl Buffer {   //{{{
             match lines_iter.next_back() {
                 Some( line ) => {
                     if re.is_match( line.as_str() ) {
-                        return Some( index );
+                        return Ok( Some( index ));
                     }
                 },
                 None => break,
@@ -600,14 +856,28 @@ impl Buffer {   //{{{
             match lines_iter.next_back() {
                 Some( line ) => {
                     if re.is_match( line.as_str() ) {
-                        return Some( index );
+                        return Ok( Some( index ));
                     }
                 },
-                None => return None,
+                None => return Ok( None ),
             }
             index -= 1;
         }
-        None
+        Ok( None )
+    }// }}}
+// }}}
+    /// Replace buffer content with lines recovered from a journal// {{{
+    ///
+    /// The buffer is marked modified, since the recovered lines have not
+    /// been saved to the working file
+    pub fn restore_lines( &mut self, lines: LineStore )// {{{
+            -> Result<(), RedError> {
+        self.lines = lines;
+        self.total_lines = self.lines.len();
+        self.current_line = self.total_lines;
+        self.markers = vec!( 0; NUM_LC );
+        self._is_modified = true;
+        self.store_buffer()
     }// }}}
 // }}}
     /// Prepare for closing buffer// {{{
@@ -620,6 +890,13 @@ impl Buffer {   //{{{
                                                     RedError::FileRemove(e) ));
         Ok( () )
     }//}}}
+// }}}
+    /// Remove temp file, whether or not changes are saved// {{{
+    pub fn discard_journal( &mut self )
+            -> Result<(), RedError> {// {{{
+        fs::remove_file( &self.buffer_file ).map_err(|e|
+                                                    RedError::FileRemove(e) )
+    }//}}}
 // }}}
     /// Deconstruct buffer// {{{
     /// I'm not sure we need this function, since opening a new
@@ -646,7 +923,7 @@ impl Buffer {   //{{{
         self.current_line = 0;
         self.total_lines = 0;
         self._is_modified = false;
-        //XXX: self.last_update = get_null_time();
+        self.last_update = None;
         self.last_temp_write = get_null_time();
         self.last_write = get_null_time();
     }// }}}
@@ -675,17 +952,26 @@ impl Buffer {   //{{{
     }
 // }}}
     /// make substitution in range of lines// {{{
+    ///
+    /// Buffer is unchanged if to_match is not a valid regex; a newline in
+    /// the replacement splits the line, so returns number of lines added
     pub fn substitute( &mut self, to_match: &str, to_sub: &str,// {{{
                        which: WhichMatch,
-                       address_initial: usize, address_final: usize ) {
-        let re: Regex = Regex::new( to_match ).unwrap();
-        for line in address_initial .. address_final + 1 {
-            self._substitute_line( line, &re, to_sub, &which );
+                       address_initial: usize, address_final: usize )
+            -> Result<usize, RedError> {
+        let re: Regex = try!( compile_regex( to_match ));
+        let mut line = address_initial;
+        let mut _final = address_final;
+        while line <= _final {
+            let added = self._substitute_line( line, &re, to_sub, &which );
+            line += 1 + added;
+            _final += added;
         }
+        Ok( _final - address_final )
     }// }}}
 // }}}
     fn _substitute_line( &mut self, address: usize, re_to_match: &Regex,// {{{
-                     to_sub: &str, which: &WhichMatch ) {
+                     to_sub: &str, which: &WhichMatch ) -> usize {
         let mut new_line: String = String::new();
         {   // create wrapping namespace
             let line_content = self.get_line_content( address )
@@ -727,9 +1013,16 @@ impl Buffer {   //{{{
             }
         }
         // Approach 1 - set line content regardless, sometimes to same
-        self.set_line_content( address, &new_line )
+        let mut pieces = new_line.split( '\n' );
+        self.set_line_content( address, pieces.next().unwrap_or( "" ))
             .expect("error setting line content");
         // Approach 2 - repeat the above match on sub_parms.which
+        let mut added: usize = 0;
+        for piece in pieces {
+            self.append_line( address + added, piece );
+            added += 1;
+        }
+        added
     }// }}}
     pub fn join_lines( &mut self,
                        address_initial: usize, address_final: usize )
@@ -754,7 +1047,6 @@ impl Buffer {   //{{{
             self.append_line( address_initial - 1, &new_line );
             self.current_line = address_initial;
         }
-        try!( self.store_buffer() );
         Ok( () )
     }
     pub fn move_lines( &mut self, address_initial: &usize,// {{{
@@ -817,6 +1109,7 @@ impl Clone for Buffer {
         Buffer{
             lines: _lines,
             buffer_file: self.buffer_file.clone(),
+            journal: self.journal.clone(),
             file: _file,
             markers: _markers,
             _is_modified: self._is_modified,
@@ -824,6 +1117,9 @@ impl Clone for Buffer {
             total_lines: self.total_lines,
             last_write: _last_write,
             last_temp_write: _last_temp_write,
+            restricted: self.restricted,
+            format: self.format,
+            last_update: self.last_update.clone(),
         }
     }
 }
@@ -859,6 +1155,100 @@ pub fn random_string() -> String {
     let result: String = thread_rng().gen_ascii_chars().take(8).collect();
     result
 }
+/// Compile regex, reporting a bad one as an error rather than panicking// {{{
+pub fn compile_regex( regex: &str ) -> Result<Regex, RedError> {// {{{
+    Regex::new( regex ).map_err( |e| RedError::Regex(e) )
+}// }}}
+// }}}
+/// Return temp file left behind for file by an earlier session, if any// {{{
+///
+/// Must be called before a Buffer is opened on the file, since that
+/// replaces the temp file with one of its own
+pub fn recovery_file<S: AsRef<OsStr> + ?Sized>( file_name: &S )// {{{
+        -> Option<OsString> {
+    let temp_file = temp_file_name( Some( file_name ));
+    if Path::new( &temp_file ).is_file() {
+        Some( temp_file )
+    } else {
+        None
+    }
+}// }}}
+// }}}
+/// Return stamp of file, or None if it doesn't exist// {{{
+///
+/// If size and modification time match known stamp, it is returned
+/// without reading the file
+fn file_stamp<S: AsRef<OsStr> + ?Sized>( path: &S,// {{{
+        known: Option<&FileStamp> ) -> io::Result<Option<FileStamp>> {
+    let metadata = match fs::metadata( path.as_ref() ) {
+        Ok( x ) => x,
+        Err( ref e ) if e.kind() == io::ErrorKind::NotFound =>
+            return Ok( None ),
+        Err( e ) => return Err( e ),
+    };
+    let modified = try!( metadata.modified() );
+    if let Some( stamp ) = known {
+        if stamp.modified == modified && stamp.size == metadata.len() {
+            return Ok( Some( stamp.clone() ));
+        }
+    }
+    let mut content: Vec<u8> = Vec::new();
+    try!( try!( File::open( path.as_ref() )).read_to_end( &mut content ));
+    Ok( Some( FileStamp::new( modified, &content )))
+}// }}}
+// }}}
+/// Return path with any symbolic links at the end followed// {{{
+///
+/// Unlike canonicalize, the file linked to needn't exist yet
+fn resolve_links( path: &Path ) -> io::Result<PathBuf> {// {{{
+    let mut path = path.to_path_buf();
+    for _ in 0 .. MAX_LINKS {
+        match fs::symlink_metadata( &path ) {
+            Ok( ref metadata ) if metadata.file_type().is_symlink() => {
+                let target = try!( fs::read_link( &path ));
+                // relative target is relative to directory of link
+                path = match path.parent() {
+                    Some( dir ) => dir.join( target ),
+                    None => target,
+                };
+            },
+            _ => return Ok( path ),
+        }
+    }
+    Err( io::Error::new( io::ErrorKind::Other,
+                         "too many levels of symbolic links" ))
+}// }}}
+// }}}
+/// Return error if path leaves current directory, as in restricted mode// {{{
+///
+/// A symbolic link must lead to a file in the current directory too
+fn check_restricted_path<S: AsRef<OsStr> + ?Sized>( path: &S )// {{{
+        -> Result<(), RedError> {
+    let name = path.as_ref().to_string_lossy();
+    if name.contains( '/' ) || name.contains( ".." ) {
+        return Err( RedError::Restricted{ parameter: name.into_owned() } );
+    }
+    match resolve_links( Path::new( path.as_ref() )) {
+        Ok( ref target ) if is_in_current_dir( target ) => Ok( () ),
+        _ => Err( RedError::Restricted{ parameter: name.into_owned() } ),
+    }
+}// }}}
+// }}}
+/// Return true if path names a file in current directory// {{{
+///
+/// The file needn't exist, but its directory must
+fn is_in_current_dir( path: &Path ) -> bool {// {{{
+    let dir = match path.parent() {
+        Some( d ) if !d.as_os_str().is_empty() => d,
+        _ => return true,
+    };
+    match ( dir.canonicalize(),
+            env::current_dir().and_then( |x| x.canonicalize() )) {
+        ( Ok( x ), Ok( y )) => x == y,
+        _ => false,
+    }
+}// }}}
+// }}}
 /// Produce name for temporary buffer storage// {{{
 ///
 /// # Panics
@@ -872,10 +1262,14 @@ fn temp_file_name<S: AsRef<OsStr> + ?Sized>( path_str: Option<&S> )
     // only way to conflict is by choosing the same eight alphanumeric
     // characters in less than a second!
     let _random_string = random_string();
-    let mut path: PathBuf = Path::new( path_str.map( |s| s.as_ref() )
-                                       .unwrap_or(OsStr::new("temp")) )
-                                       .to_path_buf();
-    let mut _temp_file_name = OsStr::new(".red.").to_os_string();
+    // buffers without a file get unique names, so several can be open
+    let mut path: PathBuf = match path_str {
+        Some( s ) => Path::new( s ).to_path_buf(),
+        None => Path::new( &( "temp.".to_string() + _random_string.as_str() +
+                              "." + &get_timestamp() )).to_path_buf(),
+    };
+    let mut _temp_file_name = OsStr::new( journal::TEMP_FILE_PREFIX )
+                                    .to_os_string();
     _temp_file_name.push( path.file_name().unwrap_or(
             OsStr::new( ("temp.".to_string() + _random_string.as_str() + "." +
                        &get_timestamp()).as_str() )));
@@ -1312,7 +1706,8 @@ mod tests {// {{{
         let to_sub: &str = r#"\2 \1"#;
 
         // Apply actual test(s)
-        buffer.substitute(regex_str, to_sub, WhichMatch::Global, 1, num_lines);
+        buffer.substitute(regex_str, to_sub, WhichMatch::Global, 1, num_lines)
+            .unwrap();
         let mut count = 0_usize;
         for line in buffer.lines_iterator() {
             count += 1;
@@ -1337,7 +1732,8 @@ mod tests {// {{{
         let to_sub: &str = r#"\5 \4 \3 \2 \1"#;
 
         // Apply actual test(s)
-        buffer.substitute(regex_str, to_sub, WhichMatch::Global, 1, num_lines);
+        buffer.substitute(regex_str, to_sub, WhichMatch::Global, 1, num_lines)
+            .unwrap();
         let mut count = 0_usize;
         for line in buffer.lines_iterator() {
             count += 1;
@@ -1362,7 +1758,7 @@ mod tests {// {{{
 
         // Apply actual test(s)
         buffer.substitute( regex_str, to_sub, WhichMatch::Number(1),
-                           1, num_lines );
+                           1, num_lines ).unwrap();
         let mut count = 0_usize;
         for line in buffer.lines_iterator() {
             count += 1;
@@ -1387,7 +1783,7 @@ mod tests {// {{{
 
         // Apply actual test(s)
         buffer.substitute( regex_str, to_sub, WhichMatch::Number(3),
-                           1, num_lines );
+                           1, num_lines ).unwrap();
         let mut count = 0_usize;
         for line in buffer.lines_iterator() {
             count += 1;
@@ -1412,7 +1808,7 @@ mod tests {// {{{
 
         // Apply actual test(s)
         buffer.substitute( regex_str, to_sub, WhichMatch::Global,
-                           1, num_lines );
+                           1, num_lines ).unwrap();
         let mut count = 0_usize;
         for line in buffer.lines_iterator() {
             count += 1;
@@ -1434,10 +1830,165 @@ mod tests {// {{{
         let expectation: String = "txstfilx linx numbxr8".to_string();
 
         // Apply actual test(s)
-        buffer.substitute( regex_str, to_sub, WhichMatch::Global, 8, 8 );
+        buffer.substitute( regex_str, to_sub, WhichMatch::Global, 8, 8 ).unwrap();
         assert_eq!( expectation, buffer.get_line_content(8).unwrap() );
         close_file_buffer_test( &mut buffer );
     }// }}}
+    /// Read command output into middle of buffer// {{{
+    #[test]
+    fn read_lines_test_1() {// {{{
+        let test_num: u8 = 7;
+        let mut buffer = open_file_buffer_test( test_num );
+        let num_lines = buffer.num_lines();
+        buffer.set_marker( 'a', 5 );
+        let ( lines_read, bytes_read, _ ) = buffer.read_lines( 3,
+                &BufferInput::Command( "echo -e one\\ntwo".to_string() ))
+            .unwrap();
+        assert_eq!( ( lines_read, bytes_read ), ( 2, 8 ));
+        assert_eq!( buffer.num_lines(), num_lines + 2 );
+        assert_eq!( buffer.get_line_content( 4 ).unwrap(), "one" );
+        assert_eq!( buffer.get_line_content( 5 ).unwrap(), "two" );
+        assert_eq!( buffer.get_current_address(), 5 );
+        assert_eq!( buffer.get_marked_line( 'a' ), 7 );
+        close_file_buffer_test( &mut buffer );
+    }// }}}
+// }}}
+    /// Replaying journal after edits reproduces buffer// {{{
+    #[test]
+    fn journal_test_1() {// {{{
+        let test_num: u8 = 8;
+        let mut buffer = open_file_buffer_test( test_num );
+        buffer.append_line( 0, "first" );
+        buffer.delete_line( 5 ).unwrap();
+        buffer.set_line_content( 3, "third" ).unwrap();
+        buffer.flush_journal().unwrap();
+        buffer.join_lines( 7, 9 ).unwrap();
+        buffer.sync_journal( FlushPolicy::OnQuit ).unwrap();
+        let replayed = journal::replay( &buffer.buffer_file ).unwrap();
+        assert!( !replayed.iter().eq( buffer.lines_iterator() ));
+        buffer.sync_journal( FlushPolicy::EveryCommand ).unwrap();
+        let replayed = journal::replay( &buffer.buffer_file ).unwrap();
+        assert!( replayed.iter().eq( buffer.lines_iterator() ));
+        buffer.store_buffer().unwrap();
+        let replayed = journal::replay( &buffer.buffer_file ).unwrap();
+        assert!( replayed.iter().eq( buffer.lines_iterator() ));
+        close_file_buffer_test( &mut buffer );
+    }// }}}
+// }}}
+    /// Unsaved changes left in temp file are restored to new buffer// {{{
+    #[test]
+    fn recovery_test_1() {// {{{
+        let test_num: u8 = 9;
+        let mut buffer = open_file_buffer_test( test_num );
+        let file_name = buffer.get_file_path().unwrap().to_os_string();
+        buffer.delete_line( 2 ).unwrap();
+        buffer.append_line( 4, "recover me" );
+        buffer.flush_journal().unwrap();
+        // buffer is abandoned without on_close, as in a crash
+        let temp_file = recovery_file( &file_name ).unwrap();
+        let lines = journal::replay( &temp_file ).unwrap();
+        let mut recovered = Buffer::new( BufferInput::File(
+                file_name.to_str().unwrap().to_string() )).unwrap();
+        recovered.restore_lines( lines ).unwrap();
+        assert!( recovered.is_modified() );
+        assert_eq!( recovered.num_lines(), buffer.num_lines() );
+        assert!( recovered.lines_iterator().eq( buffer.lines_iterator() ));
+        assert_eq!( recovered.get_line_content( 5 ).unwrap(), "recover me" );
+        close_file_buffer_test( &mut buffer );
+    }// }}}
+// }}}
+    /// Write replaces file linked to, keeping its permissions// {{{
+    #[cfg(unix)]
+    #[test]
+    fn write_to_disk_test_1() {// {{{
+        use std::env;
+        use std::os::unix::fs::{symlink, PermissionsExt};
+        let dir = env::temp_dir().join( format!( "red-write-{}",
+                                                 process::id() ));
+        fs::create_dir_all( &dir ).unwrap();
+        let file = dir.join( "file.txt" );
+        let link = dir.join( "link.txt" );
+        fs::write( &file, "one\n" ).unwrap();
+        fs::set_permissions( &file, fs::Permissions::from_mode( 0o640 ))
+            .unwrap();
+        symlink( "file.txt", &link ).unwrap();
+        let mut buffer = Buffer::new( BufferInput::File(
+                link.to_str().unwrap().to_string() )).unwrap();
+        buffer.append_line( 1, "two" );
+        buffer.write_to_disk( link.as_os_str(), false, 1, 2 ).unwrap();
+        assert!( fs::symlink_metadata( &link ).unwrap()
+                 .file_type().is_symlink() );
+        assert_eq!( fs::read_to_string( &file ).unwrap(), "one\ntwo\n" );
+        assert_eq!( fs::metadata( &file ).unwrap().permissions().mode()
+                    & 0o777, 0o640 );
+        buffer.write_to_disk( file.as_os_str(), true, 2, 2 ).unwrap();
+        assert_eq!( fs::read_to_string( &file ).unwrap(),
+                    "one\ntwo\ntwo\n" );
+        // nothing left behind but the file and the link
+        buffer.discard_journal().unwrap();
+        assert_eq!( fs::read_dir( &dir ).unwrap().count(), 2 );
+        fs::remove_dir_all( &dir ).unwrap();
+    }// }}}
+// }}}
+    /// Failed write leaves file as it was// {{{
+    #[cfg(unix)]
+    #[test]
+    fn write_to_disk_test_2() {// {{{
+        use std::env;
+        use std::os::unix::fs::PermissionsExt;
+        let dir = env::temp_dir().join( format!( "red-readonly-{}",
+                                                 process::id() ));
+        fs::create_dir_all( &dir ).unwrap();
+        let file = dir.join( "file.txt" );
+        fs::write( &file, "one\n" ).unwrap();
+        let mut buffer = Buffer::new( BufferInput::File(
+                file.to_str().unwrap().to_string() )).unwrap();
+        buffer.append_line( 1, "two" );
+        fs::set_permissions( &file, fs::Permissions::from_mode( 0o444 ))
+            .unwrap();
+        // permissions mean nothing to root
+        if fs::OpenOptions::new().write( true ).open( &file ).is_err() {
+            assert!( buffer.write_to_disk( "", false, 1, 2 ).is_err() );
+            assert_eq!( fs::read_to_string( &file ).unwrap(), "one\n" );
+        }
+        buffer.discard_journal().unwrap();
+        assert_eq!( fs::read_dir( &dir ).unwrap().count(), 1 );
+        fs::remove_dir_all( &dir ).unwrap();
+    }// }}}
+// }}}
+    /// Restricted mode refuses links to files outside current directory// {{{
+    #[cfg(unix)]
+    #[test]
+    fn restricted_test_1() {// {{{
+        use std::env;
+        use std::os::unix::fs::symlink;
+        let outside = env::temp_dir().join( format!( "red-outside-{}.txt",
+                                                     process::id() ));
+        fs::write( &outside, "one\n" ).unwrap();
+        let link_out = format!( "red-link-out-{}", process::id() );
+        let link_in = format!( "red-link-in-{}", process::id() );
+        symlink( &outside, &link_out ).unwrap();
+        symlink( "Cargo.toml", &link_in ).unwrap();
+        let mut buffer = Buffer::new( BufferInput::None ).unwrap();
+        buffer.restrict();
+        match buffer.check_input( &BufferInput::File( link_out.clone() )) {
+            Err( RedError::Restricted{ .. } ) => {},
+            _ => panic!( "expected Restricted" ),
+        }
+        buffer.check_input( &BufferInput::File( link_in.clone() )).unwrap();
+        buffer.append_line( 0, "two" );
+        match buffer.write_to_disk( &link_out, false, 1, 1 ) {
+            Err( RedError::Restricted{ .. } ) => {},
+            _ => panic!( "expected Restricted" ),
+        }
+        assert_eq!( fs::read_to_string( &outside ).unwrap(), "one\n" );
+        assert!( buffer.set_file( &link_in ).is_ok() );
+        buffer.discard_journal().unwrap();
+        fs::remove_file( &link_out ).unwrap();
+        fs::remove_file( &link_in ).unwrap();
+        fs::remove_file( &outside ).unwrap();
+    }// }}}
+// }}}
 // }}}
     /*
     #[test]
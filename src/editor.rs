@@ -0,0 +1,596 @@
+/*
+ * File   : editor.rs
+ * Purpose: public interface for editing text with ed commands
+ * Program: red
+ * About  : command-line text editor
+ * Authors: Tommy Lincoln <pajamapants3000@gmail.com>
+ * License: MIT; See LICENSE!
+ * Notes  : Notes on successful compilation
+ * Created: 10/17/2026
+ */
+
+//! Editing session for use by other programs
+//!
+//! An `Editor` holds a buffer and everything the ed commands remember
+//! between them: current address, markers, last regex, undo history.
+//! Input is handed over one line at a time with `execute`, or as a whole
+//! script with `run`; either way it is what would be typed at the prompt,
+//! text included while in Insert mode. Input is read, and output of
+//! commands such as `p` written, through a `Terminal`: standard input and
+//! output unless another, such as a `MemoryTerminal`, is set. The
+//! explanation of the last error is kept.
+// *** Bring in to namespace *** {{{
+use buf::{Buffer, BufferInput};
+use error::*;
+use io::{Terminal, get_input};
+use ops::Operations;
+use parse::{Command, parse_command, parse_invocation, recoverable_sessions};
+use ::{EditorState, EditorMode, print_error};
+// ^^^ Bring in to namespace ^^^ }}}
+
+// *** Data Structures *** {{{
+/// Editing session: a buffer and the commands to edit it// {{{
+pub struct Editor {// {{{
+    state: EditorState,
+    operations: Operations,
+}// }}}
+impl Editor {
+    /// Start session with an empty buffer, not named after any file// {{{
+    pub fn new() -> Result<Editor, RedError> {// {{{
+        Editor::from_input( BufferInput::None )
+    }// }}}
+    // }}}
+    /// Start session on given text, one line per line of text// {{{
+    pub fn from_text( text: &str ) -> Result<Editor, RedError> {// {{{
+        Editor::from_input( BufferInput::Text( text.to_string() ))
+    }// }}}
+    // }}}
+    /// Start session on a file, or on output of `@command`, as `e` would// {{{
+    pub fn open( source: &str ) -> Result<Editor, RedError> {// {{{
+        let mut editor = try!( Editor::new() );
+        try!( editor.edit( source ));
+        Ok( editor )
+    }// }}}
+    // }}}
+    /// Start session configured by command-line arguments// {{{
+    ///
+    /// batch says whether commands come from a script, as when standard
+    /// input is not a terminal; the `-b` flag selects batch mode too. The
+    /// file named on the command line is not opened until load_source
+    pub fn from_invocation( args: Vec<String>, batch: bool )// {{{
+            -> Result<Editor, RedError> {
+        let mut editor = try!( Editor::new() );
+        editor.state.batch = batch;
+        parse_invocation( args, &mut editor.state );
+        if editor.state.batch {
+            editor.state.show_prompt = false;
+        }
+        Ok( editor )
+    }// }}}
+    // }}}
+    /// Start session on given buffer content// {{{
+    fn from_input( content: BufferInput ) -> Result<Editor, RedError> {// {{{
+        let buffer = try!( Buffer::new( content ));
+        Ok( Editor{ state: EditorState::new( buffer ),
+                    operations: Operations::new() })
+    }// }}}
+    // }}}
+    /// Open file or command named on the command line, if any// {{{
+    pub fn load_source( &mut self ) -> Result<(), RedError> {// {{{
+        if self.state.source.is_empty() {
+            return self.state.buffer.set_file( "untitled" );
+        }
+        let source = self.state.source.clone();
+        self.edit( &source )
+    }// }}}
+    // }}}
+    /// Replace buffer with file or command output, using `e`// {{{
+    fn edit( &mut self, source: &str ) -> Result<(), RedError> {// {{{
+        let command = Command{ address_initial: 0, address_final: 0,
+                operation: 'e', parameters: source,
+                print_suffix: None, operations: &self.operations };
+        self.operations.execute( &mut self.state, command )
+    }// }}}
+    // }}}
+    /// Handle one line of input, as typed at the prompt// {{{
+    ///
+    /// In Command mode this is a command; lines of a command continued with
+    /// a trailing backslash are joined by newlines. In Insert mode it is a
+    /// line of text, or a single dot to return to Command mode.
+    ///
+    /// Returns RedError::Quit when the `q` command ends the session; other
+    /// errors are explained by last_message
+    pub fn execute( &mut self, input: &str ) -> Result<(), RedError> {// {{{
+        let result = match self.state.mode {
+            EditorMode::Command => self.command( input ),
+            EditorMode::Insert => {
+                self.insert( input );
+                Ok( () )
+            },
+        };
+        match self.state.mode {
+            EditorMode::Command => self.state.u_unlock(),
+            EditorMode::Insert => {},
+        }
+        if let Err( RedError::Quit ) = result {
+            // temp file is gone; don't write it again
+            return result;
+        }
+        let synced = self.state.buffer.sync_journal( self.state.flush_policy );
+        let result = result.and( synced );
+        if let Err( ref e ) = result {
+            self.state.last_help = error_message( e );
+        }
+        result
+    }// }}}
+    // }}}
+    /// Parse and execute command// {{{
+    fn command( &mut self, input: &str ) -> Result<(), RedError> {// {{{
+        if input.is_empty() {
+            return Ok( () );
+        }
+        let command = match parse_command( input, &mut self.state,
+                                           &self.operations ) {
+            Ok( x ) => x,
+            Err( e ) => {
+                self.state.quit_warned = false;
+                return Err( e );
+            },
+        };
+        let operation = command.operation;
+        let result = self.operations.execute( &mut self.state, command );
+        if operation != 'q' {
+            self.state.quit_warned = false;
+        }
+        result
+    }// }}}
+    // }}}
+    /// Add line of text after current line, or leave Insert mode// {{{
+    fn insert( &mut self, input: &str ) {// {{{
+        if input == "." {
+            self.state.mode = EditorMode::Command;
+        } else {
+            self.state.buffer.append_here( input );
+            self.state.u_added_current_line();
+        }
+    }// }}}
+    // }}}
+    /// Run script of commands and text, stopping at first error// {{{
+    ///
+    /// Lines are handled as by execute; a line ending in a backslash
+    /// continues on the next, as at the prompt
+    pub fn run( &mut self, script: &str ) -> Result<(), RedError> {// {{{
+        let mut input = String::new();
+        for line in script.lines() {
+            input.push_str( line );
+            if input.ends_with( '\\' ) {
+                input.pop();
+                input.push( '\n' );
+                continue;
+            }
+            try!( self.execute( &input ));
+            input.clear();
+        }
+        if !input.is_empty() {
+            try!( self.execute( &input ));
+        }
+        Ok( () )
+    }// }}}
+    // }}}
+    /// Read and execute input until quit; return exit status// {{{
+    ///
+    /// Errors are reported and editing goes on, except in batch mode,
+    /// where the first one ends the session
+    pub fn session( &mut self ) -> i32 {// {{{
+        loop {
+            let result = match self.read_input() {
+                Ok( input ) => self.execute( &input ),
+                Err( RedError::Quit ) => self.end_of_input(),
+                Err( e ) => Err( e ),
+            };
+            match result {
+                Ok( () ) => {},
+                Err( RedError::Quit ) => return exit_status( &RedError::Quit ),
+                Err( e ) => if let Some( status ) = self.fail( e ) {
+                    return status;
+                },
+            }
+        }
+    }// }}}
+    // }}}
+    /// Report failed command; a script ends at its first failure// {{{
+    ///
+    /// In batch mode returns exit status to end session with: 1, or 2 if
+    /// reading or writing failed, as POSIX specifies for ed reading commands
+    /// from a file. Unsaved changes made by a script are not kept for
+    /// recovery; running the script again makes them again
+    pub fn fail( &mut self, error: RedError ) -> Option<i32> {// {{{
+        let status = exit_status( &error );
+        self.report( error );
+        if self.state.batch {
+            self.close();
+            return Some( status );
+        }
+        None
+    }// }}}
+    // }}}
+    /// Handle end of input// {{{
+    ///
+    /// Insert mode ends, as with a single dot; in Command mode this is `q`
+    pub fn end_of_input( &mut self ) -> Result<(), RedError> {// {{{
+        match self.state.mode {
+            EditorMode::Insert => self.execute( "." ),
+            EditorMode::Command => self.execute( "q" ),
+        }
+    }// }}}
+    // }}}
+    /// Use given terminal for input and output from now on// {{{
+    pub fn set_terminal( &mut self, terminal: Box<dyn Terminal> ) {// {{{
+        self.state.terminal = terminal;
+    }// }}}
+    // }}}
+    /// Read next line of input from terminal, prompting as configured// {{{
+    pub fn read_input( &mut self ) -> Result<String, RedError> {// {{{
+        get_input( String::new(), &mut self.state )
+    }// }}}
+    // }}}
+    /// Show error as configured, keeping explanation for last_message// {{{
+    pub fn report( &mut self, error: RedError ) {// {{{
+        print_error( &mut self.state, error );
+    }// }}}
+    // }}}
+    /// Close buffer, discarding unsaved changes and its temp file// {{{
+    pub fn close( &mut self ) {// {{{
+        let _ = self.state.buffer.discard_journal();
+    }// }}}
+    // }}}
+    /// Return copy of all lines in buffer// {{{
+    pub fn lines( &self ) -> Vec<String> {// {{{
+        self.state.buffer.lines_iterator().map( |x| x.to_string() ).collect()
+    }// }}}
+    // }}}
+    /// Return number of lines in buffer// {{{
+    pub fn num_lines( &self ) -> usize {// {{{
+        self.state.buffer.num_lines()
+    }// }}}
+    // }}}
+    /// Return current address// {{{
+    pub fn current_address( &self ) -> usize {// {{{
+        self.state.buffer.get_current_address()
+    }// }}}
+    // }}}
+    /// Return true if buffer has changes not yet written// {{{
+    pub fn is_modified( &self ) -> bool {// {{{
+        self.state.buffer.is_modified()
+    }// }}}
+    // }}}
+    /// Refuse shell commands and files outside current directory// {{{
+    ///
+    /// As the `-R` flag does; restricted mode can't be turned off again
+    pub fn restrict( &mut self ) {// {{{
+        self.state.buffer.restrict();
+    }// }}}
+    // }}}
+    /// Return true if in restricted mode// {{{
+    pub fn is_restricted( &self ) -> bool {// {{{
+        self.state.buffer.is_restricted()
+    }// }}}
+    // }}}
+    /// Return true if commands come from a script// {{{
+    pub fn is_batch( &self ) -> bool {// {{{
+        self.state.batch
+    }// }}}
+    // }}}
+    /// List sessions that can be recovered, if `-r` asked for them// {{{
+    ///
+    /// Returns None if the flag wasn't given; otherwise the buffer is
+    /// closed first, so its own temp file is not listed, and one line is
+    /// returned for each session in the directory given to the flag
+    pub fn recoverable_sessions( &mut self )// {{{
+            -> Option<Result<Vec<String>, RedError>> {
+        let dir = match self.state.recovery_dir {
+            Some( ref x ) => x.clone(),
+            None => return None,
+        };
+        self.close();
+        Some( recoverable_sessions( &dir ))
+    }// }}}
+    // }}}
+    /// Return explanation of last error, as printed by `h`// {{{
+    pub fn last_message( &self ) -> &str {// {{{
+        &self.state.last_help
+    }// }}}
+    // }}}
+}
+// }}}
+// ^^^ Data Structures ^^^ }}}
+
+#[cfg(test)]
+mod tests {// {{{
+    use super::*;
+    use std::{env, fs, process};
+    use io::MemoryTerminal;
+
+    /// Script adds, changes, and undoes text// {{{
+    #[test]
+    fn editor_test_1() {// {{{
+        let mut editor = Editor::from_text( "one\ntwo\n" ).unwrap();
+        assert_eq!( editor.current_address(), 2 );
+        editor.run( "1a\nuno\n.\n$s/o/0/\n" ).unwrap();
+        assert_eq!( editor.lines(), vec!( "one", "uno", "tw0" ));
+        assert!( editor.is_modified() );
+        editor.execute( "u" ).unwrap();
+        assert_eq!( editor.lines(), vec!( "one", "uno", "two" ));
+        editor.run( "1s/n/\\\nN/" ).unwrap();
+        assert_eq!( editor.num_lines(), 4 );
+        editor.close();
+    }// }}}
+    // }}}
+    /// Script stops at first error, keeping its explanation// {{{
+    #[test]
+    fn editor_test_2() {// {{{
+        let mut editor = Editor::from_text( "one\n" ).unwrap();
+        match editor.run( "1d\nZ\n1d\n" ) {
+            Err( RedError::InvalidOperation{ operation: 'Z' } ) => {},
+            _ => panic!( "expected InvalidOperation" ),
+        }
+        assert_eq!( editor.last_message(), "unknown command: Z" );
+        assert_eq!( editor.num_lines(), 0 );
+        editor.close();
+    }// }}}
+    // }}}
+    /// Quitting with unsaved changes takes a second `q`// {{{
+    #[test]
+    fn editor_test_3() {// {{{
+        let mut editor = Editor::from_text( "one\n" ).unwrap();
+        editor.execute( "1d" ).unwrap();
+        match editor.execute( "q" ) {
+            Err( RedError::NoDestruct ) => {},
+            _ => panic!( "expected NoDestruct" ),
+        }
+        editor.execute( "a" ).unwrap();
+        match editor.end_of_input() {
+            Ok( () ) => {},
+            _ => panic!( "expected end of Insert mode" ),
+        }
+        match editor.end_of_input() {
+            Err( RedError::NoDestruct ) => {},
+            _ => panic!( "expected NoDestruct after other command" ),
+        }
+        match editor.end_of_input() {
+            Err( RedError::Quit ) => {},
+            _ => panic!( "expected Quit" ),
+        }
+        editor.close();
+    }// }}}
+    // }}}
+    /// Whole session read from and written to memory, with prompts// {{{
+    #[test]
+    fn editor_test_4() {// {{{
+        let terminal = MemoryTerminal::new( "2p\n,n\n$l\nH\nZ\nh\n=\nq\n" );
+        let mut editor = Editor::from_text( "one\ntwo\tx\n" ).unwrap();
+        editor.set_terminal( Box::new( terminal.clone() ));
+        assert_eq!( editor.session(), 0 );
+        assert_eq!( terminal.take_output(),
+                    "%two\tx\n%1|one\n2|two\tx\n".to_string() +
+                    "%!two\\tx$\n%help output set to \"off\"\n%?\n" +
+                    "%unknown command: Z\n%2\n%" );
+        assert_eq!( terminal.take_output(), "" );
+    }// }}}
+    // }}}
+    /// Terminal size sets pages of `l` and `z`; pager doesn't eat input// {{{
+    #[test]
+    fn editor_test_5() {// {{{
+        let terminal = MemoryTerminal::new( "q\n" );
+        terminal.set_size( 10, 3 );
+        let long_line = "a".repeat( 25 );
+        let mut editor = Editor::from_text(
+                &( long_line.clone() + "\nb\nc\n" )).unwrap();
+        editor.set_terminal( Box::new( terminal.clone() ));
+        editor.execute( "1l" ).unwrap();
+        assert_eq!( terminal.take_output(),
+                    "a".repeat( 20 ) + "\n" + "aaaaa$\n" );
+        editor.execute( "1z" ).unwrap();
+        assert_eq!( terminal.take_output(), long_line + "\nb\n" );
+        assert_eq!( editor.read_input().unwrap(), "q" );
+        match editor.read_input() {
+            Err( RedError::Quit ) => {},
+            _ => panic!( "expected end of input" ),
+        }
+        editor.close();
+    }// }}}
+    // }}}
+    /// Restricted mode refuses commands and paths outside current directory// {{{
+    #[test]
+    fn editor_test_6() {// {{{
+        let mut editor = Editor::from_text( "one\n" ).unwrap();
+        editor.restrict();
+        assert!( editor.is_restricted() );
+        for script in &[ "!echo two", "1!tr a-z A-Z", "r !echo two",
+                         "e @echo two", "E ../one", "r /etc/hostname",
+                         "w /tmp/one", "W ../one", "f sub/one" ] {
+            match editor.execute( script ) {
+                Err( RedError::Restricted{ parameter: _ } ) => {},
+                _ => panic!( "expected Restricted for {}", script ),
+            }
+        }
+        assert_eq!( editor.lines(), vec!( "one" ));
+        editor.run( "a\ntwo\n.\nf restricted.txt\n" ).unwrap();
+        assert!( editor.is_restricted() );
+        assert_eq!( editor.num_lines(), 2 );
+        editor.close();
+    }// }}}
+    // }}}
+    /// File that isn't UTF-8 is listed with escapes, written back as is// {{{
+    #[test]
+    fn editor_test_7() {// {{{
+        let path = env::temp_dir().join( format!( "red-binary-{}.txt",
+                                                  process::id() ));
+        fs::write( &path, b"caf\xe9\n\xff\x00\n\xc3\xa9t\xe9\n" ).unwrap();
+        let terminal = MemoryTerminal::default();
+        let mut editor = Editor::open( path.to_str().unwrap() ).unwrap();
+        editor.set_terminal( Box::new( terminal.clone() ));
+        editor.run( ",l\n1s/caf/CAF/\n3s/t/T/\nw\n" ).unwrap();
+        assert_eq!( terminal.take_output(),
+                    "!caf\\351$\n!\\377\\u{0}$\n!\\u{e9}t\\351$\n" );
+        assert_eq!( fs::read( &path ).unwrap(),
+                    b"CAF\xe9\n\xff\x00\n\xc3\xa9T\xe9\n".to_vec() );
+        editor.close();
+        fs::remove_file( &path ).unwrap();
+    }// }}}
+    // }}}
+    /// Line endings and missing final newline are kept, or converted// {{{
+    #[test]
+    fn editor_test_8() {// {{{
+        let path = env::temp_dir().join( format!( "red-crlf-{}.txt",
+                                                  process::id() ));
+        fs::write( &path, b"one\r\ntwo" ).unwrap();
+        let terminal = MemoryTerminal::default();
+        let mut editor = Editor::open( path.to_str().unwrap() ).unwrap();
+        editor.set_terminal( Box::new( terminal.clone() ));
+        assert_eq!( editor.lines(), vec!( "one", "two" ));
+        editor.run( "1s/one/1/\nw\n" ).unwrap();
+        assert_eq!( fs::read( &path ).unwrap(), b"1\r\ntwo".to_vec() );
+        editor.run( "F\nF lf eol\nF\n" ).unwrap();
+        assert!( editor.is_modified() );
+        editor.run( "w\n" ).unwrap();
+        assert_eq!( fs::read( &path ).unwrap(), b"1\ntwo\n".to_vec() );
+        assert_eq!( terminal.take_output(), "line endings: crlf, ".to_string() +
+                    "no newline at end of file\nline endings: lf\n" );
+        editor.close();
+        fs::remove_file( &path ).unwrap();
+    }// }}}
+    // }}}
+    /// File changed by someone else is overwritten only if user agrees// {{{
+    #[test]
+    fn editor_test_9() {// {{{
+        let path = env::temp_dir().join( format!( "red-changed-{}.txt",
+                                                  process::id() ));
+        fs::write( &path, "one\n" ).unwrap();
+        let terminal = MemoryTerminal::default();
+        let mut editor = Editor::open( path.to_str().unwrap() ).unwrap();
+        editor.set_terminal( Box::new( terminal.clone() ));
+        // same content written again is no change
+        fs::write( &path, "one\n" ).unwrap();
+        editor.run( "a\ntwo\n.\nw\n" ).unwrap();
+        fs::write( &path, "changed\n" ).unwrap();
+        editor.run( "a\nthree\n.\n" ).unwrap();
+        terminal.push_answer( "n" );
+        match editor.execute( "w" ) {
+            Err( RedError::FileChanged ) => {},
+            _ => panic!( "expected FileChanged" ),
+        }
+        assert_eq!( fs::read_to_string( &path ).unwrap(), "changed\n" );
+        // quitting would lose changes, so it asks too
+        match editor.execute( "q" ) {
+            Err( RedError::FileChanged ) => {},
+            _ => panic!( "expected FileChanged" ),
+        }
+        terminal.push_answer( "y" );
+        editor.execute( "w" ).unwrap();
+        assert_eq!( fs::read_to_string( &path ).unwrap(), "one\ntwo\nthree\n" );
+        assert_eq!( terminal.take_output(), "" );
+        // what we wrote doesn't count as a change
+        editor.run( "1d\nw\n" ).unwrap();
+        editor.close();
+        fs::remove_file( &path ).unwrap();
+    }// }}}
+    // }}}
+    /// Unsaved changes left behind can be compared with the file// {{{
+    #[test]
+    fn editor_test_10() {// {{{
+        let path = env::temp_dir().join( format!( "red recover {}.txt",
+                                                  process::id() ));
+        let name = path.to_str().unwrap();
+        fs::write( &path, "one\ntwo\n" ).unwrap();
+        // left open, as if it crashed
+        let mut crashed = Editor::open( name ).unwrap();
+        crashed.execute( "2s/two/TWO/" ).unwrap();
+        let terminal = MemoryTerminal::default();
+        let mut editor = Editor::new().unwrap();
+        editor.set_terminal( Box::new( terminal.clone() ));
+        terminal.push_answer( "d" );
+        terminal.push_answer( "x" );
+        editor.execute( &format!( "e {}", name )).unwrap();
+        let output = terminal.take_output();
+        assert!( output.contains( "\n-two\n+TWO\n" ), "{}", output );
+        assert_eq!( editor.lines(), vec!( "one", "two" ));
+        editor.close();
+        crashed.close();
+        fs::remove_file( &path ).unwrap();
+    }// }}}
+    // }}}
+    /// Script cannot answer, so unsaved changes left behind stay put// {{{
+    #[test]
+    fn editor_test_13() {// {{{
+        let path = env::temp_dir().join( format!( "red pending {}.txt",
+                                                  process::id() ));
+        let name = path.to_str().unwrap();
+        fs::write( &path, "one\ntwo\n" ).unwrap();
+        let mut crashed = Editor::open( name ).unwrap();
+        crashed.execute( "2s/two/TWO/" ).unwrap();
+        let terminal = MemoryTerminal::default();
+        let mut editor = Editor::from_invocation(
+                vec!( "red".to_string(), name.to_string() ), true ).unwrap();
+        editor.set_terminal( Box::new( terminal.clone() ));
+        match editor.load_source() {
+            Err( RedError::RecoveryPending ) => {},
+            _ => panic!( "expected RecoveryPending" ),
+        }
+        let output = terminal.take_output();
+        assert!( output.contains( "open the file interactively" ), "{}",
+                 output );
+        crashed.close();
+        fs::remove_file( &path ).unwrap();
+    }// }}}
+    // }}}
+    /// Undo history does not carry over to file opened with `e`// {{{
+    #[test]
+    fn editor_test_12() {// {{{
+        let dir = env::temp_dir();
+        let name_a = format!( "red-undo-a-{}.txt", process::id() );
+        let name_b = format!( "red-undo-b-{}.txt", process::id() );
+        fs::write( dir.join( &name_a ), "a\nb\n" ).unwrap();
+        fs::write( dir.join( &name_b ), "x\ny\n" ).unwrap();
+        let mut editor = Editor::open( dir.join( &name_a ).to_str().unwrap() )
+            .unwrap();
+        editor.set_terminal( Box::new( MemoryTerminal::default() ));
+        editor.run( &format!( "1d\nw\ne {}\n",
+                              dir.join( &name_b ).to_str().unwrap() ))
+            .unwrap();
+        match editor.execute( "u" ) {
+            Err( RedError::NothingToUndo ) => {},
+            _ => panic!( "expected NothingToUndo" ),
+        }
+        match editor.execute( "U" ) {
+            Err( RedError::NothingToRedo ) => {},
+            _ => panic!( "expected NothingToRedo" ),
+        }
+        assert_eq!( editor.lines(), vec!( "x", "y" ));
+        editor.close();
+        fs::remove_file( dir.join( &name_a )).unwrap();
+        fs::remove_file( dir.join( &name_b )).unwrap();
+    }// }}}
+    // }}}
+    /// Buffer kept after failed `E` can still be recovered// {{{
+    #[test]
+    fn editor_test_11() {// {{{
+        let dir = env::temp_dir();
+        let name = format!( "red-kept-{}.txt", process::id() );
+        fs::write( dir.join( &name ), "one\n" ).unwrap();
+        let mut editor = Editor::open( dir.join( &name ).to_str().unwrap() )
+            .unwrap();
+        editor.run( "a\ntwo\n.\n" ).unwrap();
+        match editor.execute( "E @nonexistent_cmd_xyz" ) {
+            Err( RedError::CommandRun(_) ) => {},
+            _ => panic!( "expected CommandRun" ),
+        }
+        assert_eq!( editor.lines(), vec!( "one", "two" ));
+        let journal = dir.join( format!( ".red.{}", name ));
+        assert!( journal.is_file() );
+        editor.close();
+        assert!( !journal.exists() );
+        fs::remove_file( dir.join( &name )).unwrap();
+    }// }}}
+    // }}}
+}// }}}
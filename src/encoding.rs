@@ -0,0 +1,228 @@
+/*
+ * File   : encoding.rs
+ * Purpose: keep text that is not valid UTF-8 intact as lines of String
+ * Program: red
+ * About  : command-line text editor
+ * Authors: Tommy Lincoln <pajamapants3000@gmail.com>
+ * License: MIT; See LICENSE!
+ * Notes  : Notes on successful compilation
+ * Created: 10/17/2026
+ */
+
+//! Lossless conversion between bytes and String
+//!
+//! Each byte that is not part of valid UTF-8 is stored as one of the
+//! characters U+10FF80 to U+10FFFF, from the private use area, so that
+//! searching and substituting work on the rest of the line as usual.
+//! Those characters can't be stored as themselves, or they would be
+//! mistaken for bytes on the way out; any found in valid UTF-8 are
+//! escaped byte by byte too. Encoding a decoded line therefore always
+//! gives back the original bytes.
+//!
+//! How lines are terminated is detected when they are split, so a file can
+//! be written back the way it was read.
+// *** Bring in to namespace *** {{{
+use std::char;
+use std::str;
+// ^^^ Bring in to namespace ^^^ }}}
+
+// *** Constants *** {{{
+/// character standing for byte 0; only bytes from 0x80 are ever escaped
+const ESCAPE_BASE: u32 = 0x10FF00;
+/// first character used to stand for a byte
+const ESCAPE_FIRST: u32 = ESCAPE_BASE + 0x80;
+// ^^^ Constants ^^^ }}}
+
+// *** Data Structures *** {{{
+/// Sequence ending each line of a file// {{{
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {// {{{
+    Lf,
+    CrLf,
+}// }}}
+// }}}
+impl LineEnding {// {{{
+    /// Return bytes written at end of line// {{{
+    pub fn as_bytes( &self ) -> &'static [u8] {// {{{
+        match *self {
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+        }
+    }// }}}
+    // }}}
+    /// Return name used by `F` command// {{{
+    pub fn name( &self ) -> &'static str {// {{{
+        match *self {
+            LineEnding::Lf => "lf",
+            LineEnding::CrLf => "crlf",
+        }
+    }// }}}
+    // }}}
+}// }}}
+/// How lines of a file are terminated// {{{
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextFormat {// {{{
+    pub line_ending: LineEnding,
+    /// false if last line has no line ending
+    pub final_newline: bool,
+}// }}}
+// }}}
+impl Default for TextFormat {// {{{
+    fn default() -> TextFormat {// {{{
+        TextFormat{ line_ending: LineEnding::Lf, final_newline: true }
+    }// }}}
+}// }}}
+// ^^^ Data Structures ^^^ }}}
+
+// *** Functions *** {{{
+/// Return byte that character stands for, if it is an escaped byte// {{{
+pub fn escaped_byte( ch: char ) -> Option<u8> {// {{{
+    let code = ch as u32;
+    if code >= ESCAPE_FIRST {
+        Some( ( code - ESCAPE_BASE ) as u8 )
+    } else {
+        None
+    }
+}// }}}
+// }}}
+/// Push character standing for byte// {{{
+fn push_escaped( text: &mut String, byte: u8 ) {// {{{
+    debug_assert!( byte >= 0x80 );
+    text.push( char::from_u32( ESCAPE_BASE + byte as u32 )
+               .expect( "encoding::push_escaped: invalid escape" ));
+}// }}}
+// }}}
+/// Push valid UTF-8, escaping characters that would look like bytes// {{{
+fn push_valid( text: &mut String, valid: &str ) {// {{{
+    for ch in valid.chars() {
+        if escaped_byte( ch ).is_some() {
+            let mut utf8 = [0; 4];
+            for byte in ch.encode_utf8( &mut utf8 ).bytes() {
+                push_escaped( text, byte );
+            }
+        } else {
+            text.push( ch );
+        }
+    }
+}// }}}
+// }}}
+/// Return bytes as String, escaping whatever is not valid UTF-8// {{{
+pub fn decode( bytes: &[u8] ) -> String {// {{{
+    let mut text = String::with_capacity( bytes.len() );
+    let mut rest = bytes;
+    loop {
+        match str::from_utf8( rest ) {
+            Ok( valid ) => {
+                push_valid( &mut text, valid );
+                return text;
+            },
+            Err( e ) => {
+                let ( valid, invalid ) = rest.split_at( e.valid_up_to() );
+                push_valid( &mut text, unsafe {
+                    // checked by from_utf8 above
+                    str::from_utf8_unchecked( valid )
+                });
+                // None means sequence cut off by end of input
+                let bad_len = e.error_len().unwrap_or( invalid.len() );
+                for &byte in &invalid[ .. bad_len ] {
+                    push_escaped( &mut text, byte );
+                }
+                rest = &invalid[ bad_len .. ];
+            },
+        }
+    }
+}// }}}
+// }}}
+/// Return bytes that decoded text came from// {{{
+pub fn encode( text: &str ) -> Vec<u8> {// {{{
+    let mut bytes = Vec::with_capacity( text.len() );
+    let mut utf8 = [0; 4];
+    for ch in text.chars() {
+        match escaped_byte( ch ) {
+            Some( byte ) => bytes.push( byte ),
+            None => bytes.extend_from_slice(
+                ch.encode_utf8( &mut utf8 ).as_bytes() ),
+        }
+    }
+    bytes
+}// }}}
+// }}}
+/// Split bytes into decoded lines; return them and how they ended// {{{
+///
+/// Line endings are CRLF only if every line ending is; otherwise any
+/// carriage returns are kept as part of their lines, so nothing is lost
+pub fn decode_text( bytes: &[u8] ) -> ( Vec<String>, TextFormat ) {// {{{
+    let mut pieces: Vec<&[u8]> = bytes.split( |&b| b == b'\n' ).collect();
+    // split leaves an empty piece after the last newline
+    let final_newline = bytes.is_empty() || bytes.last() == Some( &b'\n' );
+    if final_newline {
+        pieces.pop();
+    }
+    let num_ended = if final_newline { pieces.len() }
+                    else { pieces.len() - 1 };
+    let crlf = num_ended > 0 && pieces[ .. num_ended ].iter()
+        .all( |piece| piece.last() == Some( &b'\r' ));
+    let lines = pieces.iter().enumerate().map( |( indx, piece )|
+        if crlf && indx < num_ended {
+            decode( &piece[ .. piece.len() - 1 ] )
+        } else {
+            decode( piece )
+        }).collect();
+    let line_ending = if crlf { LineEnding::CrLf } else { LineEnding::Lf };
+    ( lines, TextFormat{ line_ending: line_ending,
+                         final_newline: final_newline } )
+}// }}}
+// }}}
+// ^^^ Functions ^^^ }}}
+
+#[cfg(test)]
+mod tests {// {{{
+    use super::*;
+
+    /// Invalid UTF-8 and lookalike characters survive the round trip// {{{
+    #[test]
+    fn encoding_test_1() {// {{{
+        let samples: Vec<&[u8]> = vec!(
+            b"plain ascii",
+            b"caf\xe9 au lait",                 // Latin-1
+            "caf\u{e9}".as_bytes(),             // UTF-8
+            b"\xff\xfe\x00b\x00i\x00n",
+            b"cut off \xe2\x82",
+            "\u{10ff80}\u{10ffff}".as_bytes(),  // look like escapes
+            b"",
+        );
+        for bytes in samples {
+            assert_eq!( encode( &decode( bytes )), bytes );
+        }
+    }// }}}
+    // }}}
+    /// Only bytes that aren't valid UTF-8 are escaped// {{{
+    #[test]
+    fn encoding_test_2() {// {{{
+        let text = decode( b"caf\xe9 \xc3\xa9" );
+        assert_eq!( text.chars().count(), 6 );
+        assert_eq!( text.chars().nth( 3 ).and_then( escaped_byte ),
+                    Some( 0xe9 ));
+        assert!( text.ends_with( " \u{e9}" ));
+    }// }}}
+    // }}}
+    /// Lines split keeping track of how they ended// {{{
+    #[test]
+    fn decode_text_test_1() {// {{{
+        let ( lines, format ) = decode_text( b"one\ntwo\n\nthree" );
+        assert_eq!( lines, vec!( "one", "two", "", "three" ));
+        assert_eq!( format, TextFormat{ line_ending: LineEnding::Lf,
+                                        final_newline: false } );
+        let ( lines, format ) = decode_text( b"one\r\n\r\ntwo\r" );
+        assert_eq!( lines, vec!( "one", "", "two\r" ));
+        assert_eq!( format.line_ending, LineEnding::CrLf );
+        assert!( !format.final_newline );
+        let ( lines, format ) = decode_text( b"one\r\ntwo\n" );
+        assert_eq!( lines, vec!( "one\r", "two" ));
+        assert_eq!( format, TextFormat::default() );
+        assert_eq!( decode_text( b"\n" ).0, vec!( "" ));
+        assert!( decode_text( b"" ).0.is_empty() );
+        assert!( decode_text( b"" ).1.final_newline );
+    }// }}}
+    // }}}
+}// }}}
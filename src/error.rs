@@ -11,6 +11,8 @@
 
 // Bring in to namespace {{{
 use std::io;
+
+use ::regex;
 // }}}
 
 // *** Data Structures *** {{{
@@ -20,6 +22,8 @@ pub enum RedError {
     FileRename(io::Error),
     FileWrite(io::Error),
     FileRemove(io::Error),
+    FileRead(io::Error),
+    CommandRun(io::Error),
 //    FileCopy(io::Error),
 //    FileExist(io::Error),
 //    FileClose(io::Error),
@@ -34,36 +38,82 @@ pub enum RedError {
     Stdout,
 //    Stderr,
     NoDestruct,
+    FileChanged,
+    RecoveryPending,
+    NothingToUndo,
+    NothingToRedo,
+    NoPreviousRegex,
+    NoPreviousSubstitution,
+    NoMatch,
+    Regex(regex::Error),
+    Restricted{ parameter: String },
     CriticalError(String),
     Quit,
 }
 // ^^^ Data Structures ^^^ }}}
 
 // *** Functions *** {{{
-/// Return error code for given error type {{{
+/// Return exit status for session ended by given error, as for ed {{{
 ///
-pub fn error_code( _error: RedError ) -> u32 {
-    match _error {
-        RedError::FileOpen(_) => 280,
-        RedError::FileRename(_) => 281,
-        RedError::FileWrite(_) => 282,
-        RedError::FileRemove(_) => 283,
-//        RedError::FileCopy(_) => 284,
-//        RedError::FileExist(_) => 285,
-//        RedError::FileClose(_) => 286,
-        RedError::SetLineOutOfBounds{ address: _ } => 290,
-        RedError::GetLineOutOfBounds{ address: _ } => 291,
-        RedError::Stdin => 297,
-        RedError::Stdout => 298,
-//        RedError::Stderr => 299,
-//        RedError::ParseCommand => 300,
-        RedError::OpCharIndex => 301,
-        RedError::AddressSyntax{ address: _} => 302,
-        RedError::ParameterSyntax{ parameter: _} => 303,
-        RedError::InvalidOperation{ operation: _ } => 304,
-        RedError::NoDestruct => 305,
-        RedError::CriticalError(_) => 99,
+/// 1 when a command failed, 2 when reading or writing failed, which
+/// includes a file left with unsaved changes that only a person can recover
+pub fn exit_status( _error: &RedError ) -> i32 {
+    match *_error {
+        RedError::FileOpen(_) | RedError::FileRename(_) |
+        RedError::FileWrite(_) | RedError::FileRemove(_) |
+        RedError::FileRead(_) | RedError::CommandRun(_) |
+        RedError::Stdin | RedError::Stdout |
+        RedError::RecoveryPending |
+        RedError::CriticalError(_) => 2,
         RedError::Quit => 0,
+        _ => 1,
+    }
+}
+//}}}
+/// Return ed-style explanation of error for help output {{{
+///
+/// This is what `h` prints, and what is shown in place of `?` when help
+/// output is on
+pub fn error_message( _error: &RedError ) -> String {
+    match *_error {
+        RedError::FileOpen( ref e ) => format!( "cannot open file: {}", e ),
+        RedError::FileRename( ref e ) =>
+            format!( "cannot rename temp file: {}", e ),
+        RedError::FileWrite( ref e ) => format!( "cannot write file: {}", e ),
+        RedError::FileRemove( ref e ) =>
+            format!( "cannot remove file: {}", e ),
+        RedError::FileRead( ref e ) => format!( "cannot read file: {}", e ),
+        RedError::CommandRun( ref e ) =>
+            format!( "cannot run command: {}", e ),
+        RedError::SetLineOutOfBounds{ address } |
+        RedError::GetLineOutOfBounds{ address } =>
+            format!( "invalid address: no line {}", address ),
+        RedError::Stdin => "cannot read from standard input".to_string(),
+        RedError::Stdout => "cannot write to standard output".to_string(),
+        RedError::OpCharIndex => "unknown command".to_string(),
+        RedError::AddressSyntax{ ref address } =>
+            format!( "invalid address: {}", address ),
+        RedError::ParameterSyntax{ ref parameter } =>
+            format!( "invalid command suffix or parameter: {}", parameter ),
+        RedError::InvalidOperation{ operation } =>
+            format!( "unknown command: {}", operation ),
+        RedError::NoDestruct => "warning: buffer modified".to_string(),
+        RedError::FileChanged =>
+            "warning: file changed on disk since read or written".to_string(),
+        RedError::RecoveryPending =>
+            "unsaved changes from an earlier session not recovered".to_string(),
+        RedError::NothingToUndo => "nothing to undo".to_string(),
+        RedError::NothingToRedo => "nothing to redo".to_string(),
+        RedError::NoPreviousRegex => "no previous pattern".to_string(),
+        RedError::NoPreviousSubstitution =>
+            "no previous substitution".to_string(),
+        RedError::NoMatch => "no match".to_string(),
+        RedError::Regex( ref e ) =>
+            format!( "invalid regular expression: {}", e ),
+        RedError::Restricted{ ref parameter } =>
+            format!( "not allowed in restricted mode: {}", parameter ),
+        RedError::CriticalError( ref x ) => format!( "internal error: {}", x ),
+        RedError::Quit => "quit".to_string(),
     }
 }
 //}}}
@@ -77,3 +127,33 @@ pub fn assert_addresses( address_initial: usize, address_final: usize,// {{{
 // }}}
 // ^^^ Functions ^^^ }}}
 
+#[cfg(test)]
+mod tests {// {{{
+    use super::*;
+
+    /// Help messages include details carried by error// {{{
+    #[test]
+    fn error_message_test_1() {// {{{
+        assert_eq!( error_message( &RedError::InvalidOperation{
+                operation: 'Z' } ), "unknown command: Z" );
+        assert_eq!( error_message( &RedError::AddressSyntax{
+                address: "1,x".to_string() } ), "invalid address: 1,x" );
+        assert_eq!( error_message( &RedError::GetLineOutOfBounds{
+                address: 40 } ), "invalid address: no line 40" );
+        assert_eq!( error_message( &RedError::NoDestruct ),
+                    "warning: buffer modified" );
+    }// }}}
+    // }}}
+    /// Failed commands and failed reads or writes exit differently// {{{
+    #[test]
+    fn exit_status_test_1() {// {{{
+        assert_eq!( exit_status( &RedError::InvalidOperation{
+                operation: 'Z' } ), 1 );
+        assert_eq!( exit_status( &RedError::NoDestruct ), 1 );
+        assert_eq!( exit_status( &RedError::FileWrite( io::Error::new(
+                io::ErrorKind::Other, "disk full" ))), 2 );
+        assert_eq!( exit_status( &RedError::RecoveryPending ), 2 );
+        assert_eq!( exit_status( &RedError::Quit ), 0 );
+    }// }}}
+    // }}}
+}// }}}
@@ -12,14 +12,19 @@
 // *** Bring in to namespace *** {{{
 use std::fs::{File, OpenOptions};
 use std::path::Path;
-use std::process::{Command, Output};
+use std::process::{Command, Output, Stdio};
+use std::thread;
 use std::io::{self, BufRead, Write};
 use std::ffi::OsStr;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::cell::RefCell;
 
 use regex::Regex;
 
 use error::*;
-use ::{EditorState, EditorMode};
+use encoding::{decode, encode};
+use ::{EditorState, EditorMode, term_size};
 
 // ^^^ Bring in to namespace ^^^ }}}
 //
@@ -43,6 +48,122 @@ pub struct FileMode {// {{{
     pub f_create_new:   bool,
 }// }}}
 
+/// Where an editing session gets its input and sends its output// {{{
+pub trait Terminal {
+    /// Show prompt and read a line of input, without its newline// {{{
+    ///
+    /// Returns None at end of input
+    fn read_line( &mut self, prompt: &str )
+            -> Result<Option<String>, RedError>;// }}}
+    /// Write output; it includes any newlines// {{{
+    fn write( &mut self, output: &str ) -> Result<(), RedError>;// }}}
+    /// Return width and height in characters, if known// {{{
+    fn size( &self ) -> Option<( usize, usize )>;// }}}
+    /// Ask question and wait for answer; empty answer takes default// {{{
+    fn confirm( &mut self, question: &str ) -> Result<String, RedError>;// }}}
+}
+// }}}
+/// Terminal using standard input and output// {{{
+pub struct StdTerminal;
+impl Terminal for StdTerminal {
+    fn read_line( &mut self, prompt: &str )// {{{
+            -> Result<Option<String>, RedError> {
+        try!( self.write( prompt ));
+        try!( io::stdout().flush().map_err( |_| RedError::Stdout ));
+        let mut line: Vec<u8> = Vec::new();
+        let num_bytes = try!( io::stdin().lock().read_until( b'\n', &mut line )
+              .map_err( |_| RedError::Stdin ));
+        if num_bytes == 0 {
+            return Ok( None );
+        }
+        // last line of a script may lack its newline
+        if line.last() == Some( &b'\n' ) {
+            line.pop();
+        }
+        // text typed in another encoding is kept as typed
+        Ok( Some( decode( &line )))
+    }// }}}
+    fn write( &mut self, output: &str ) -> Result<(), RedError> {// {{{
+        io::stdout().write_all( &encode( output ))
+            .map_err( |_| RedError::Stdout )
+    }// }}}
+    fn size( &self ) -> Option<( usize, usize )> {// {{{
+        term_size::dimensions()
+    }// }}}
+    fn confirm( &mut self, question: &str ) -> Result<String, RedError> {// {{{
+        Ok( try!( self.read_line( question )).unwrap_or( String::new() ))
+    }// }}}
+}
+// }}}
+/// Terminal kept in memory, for tests and programs embedding the editor// {{{
+///
+/// Clones share content, so a clone kept by the caller sees the output
+/// of the one given to the editor. Prompts are recorded as output, as
+/// a terminal would show them; questions are answered from a separate
+/// queue, or with the default
+#[derive(Clone, Default)]
+pub struct MemoryTerminal {// {{{
+    content: Rc<RefCell<MemoryContent>>,
+}// }}}
+#[derive(Default)]
+struct MemoryContent {// {{{
+    input: VecDeque<String>,
+    answers: VecDeque<String>,
+    output: String,
+    size: Option<( usize, usize )>,
+}// }}}
+impl MemoryTerminal {
+    /// Create terminal whose input is given lines, of unknown size// {{{
+    pub fn new( input: &str ) -> MemoryTerminal {// {{{
+        let terminal = MemoryTerminal::default();
+        terminal.push_input( input );
+        terminal
+    }// }}}
+    // }}}
+    /// Add lines to end of input// {{{
+    pub fn push_input( &self, input: &str ) {// {{{
+        self.content.borrow_mut().input.extend(
+            input.lines().map( |x| x.to_string() ));
+    }// }}}
+    // }}}
+    /// Add answer for a question asked by confirm// {{{
+    pub fn push_answer( &self, answer: &str ) {// {{{
+        self.content.borrow_mut().answers.push_back( answer.to_string() );
+    }// }}}
+    // }}}
+    /// Set size reported to the editor// {{{
+    pub fn set_size( &self, width: usize, height: usize ) {// {{{
+        self.content.borrow_mut().size = Some(( width, height ));
+    }// }}}
+    // }}}
+    /// Return and clear output written so far// {{{
+    pub fn take_output( &self ) -> String {// {{{
+        let mut content = self.content.borrow_mut();
+        ::std::mem::replace( &mut content.output, String::new() )
+    }// }}}
+    // }}}
+}
+impl Terminal for MemoryTerminal {
+    fn read_line( &mut self, prompt: &str )// {{{
+            -> Result<Option<String>, RedError> {
+        let mut content = self.content.borrow_mut();
+        content.output.push_str( prompt );
+        Ok( content.input.pop_front() )
+    }// }}}
+    fn write( &mut self, output: &str ) -> Result<(), RedError> {// {{{
+        self.content.borrow_mut().output.push_str( output );
+        Ok( () )
+    }// }}}
+    fn size( &self ) -> Option<( usize, usize )> {// {{{
+        self.content.borrow().size
+    }// }}}
+    fn confirm( &mut self, _question: &str ) -> Result<String, RedError> {// {{{
+        Ok( self.content.borrow_mut().answers.pop_front()
+                .unwrap_or( String::new() ))
+    }// }}}
+}
+// }}}
+
 /*
 struct FileCoordinate {
     line: usize,
@@ -77,7 +198,7 @@ pub fn file_opener<S: AsRef<OsStr> + ?Sized>( name: &S, mode: FileMode )
         .create_new(mode.f_create_new)
         .open( Path::new(name) ).map_err(|err| RedError::FileOpen( err ) )
 }// }}}
-/// Get input from stdin// {{{
+/// Get input from the terminal// {{{
 ///
 /// Collects input differently depending on current mode
 /// In Command mode, collects lines until it reaches an end-of-line that
@@ -87,21 +208,19 @@ pub fn file_opener<S: AsRef<OsStr> + ?Sized>( name: &S, mode: FileMode )
 /// In View mode (NYI), collects single characters for controlling view output
 ///     e.g. j,k for scrolling down, up
 ///
-pub fn get_input( mut input_buffer: String, state: &EditorState )
+/// Returns RedError::Quit at end of input
+pub fn get_input( mut input_buffer: String, state: &mut EditorState )
             -> Result<String, RedError> {// {{{
-    let stdin = io::stdin();
-    let stdout = io::stdout();
-    let mut stdin_handle = stdin.lock();
-    let mut stdout_handle = stdout.lock();
-    let mut prompt: &str;
-
-    match state.mode {
-        EditorMode::Command => prompt = &state.prompt,
-        EditorMode::Insert  => prompt = PROMPT_INSERT,
+    let mut prompt: String = match state.mode {
+        EditorMode::Command => state.prompt.clone(),
+        EditorMode::Insert  => PROMPT_INSERT.to_string(),
+    };
+    if !state.show_prompt {
+        prompt.clear();
     }
 
     lazy_static! {
-        static ref RE: Regex = Regex::new( r#".*\\"# )
+        static ref RE: Regex = Regex::new( r#"\\$"# )
             .expect("get_input: failed to compile regex");
     }
 
@@ -115,117 +234,159 @@ pub fn get_input( mut input_buffer: String, state: &EditorState )
             None => {},
         }
 
-        try!( stdout_handle.write( prompt.as_bytes() )
-              .map_err( |_| RedError::Stdout ));
-        try!( stdout_handle.flush().map_err( |_| RedError::Stdout ));
-        try!( stdin_handle.read_line( &mut input_buffer )
-              .map_err( |_| RedError::Stdin ));
-
-        match input_buffer.pop() {
-            Some(x) => assert_eq!( x, '\n' ),
-            None => {},
+        match try!( state.terminal.read_line( &prompt )) {
+            Some( line ) => input_buffer.push_str( &line ),
+            // end of input; treated as quit
+            None if input_buffer.is_empty() => return Err( RedError::Quit ),
+            None => {
+                // drop newline of unfinished continuation
+                input_buffer.pop();
+                break;
+            },
         }
 
         if !RE.is_match( &mut input_buffer ) {
             break;
         }
 
-        prompt = PROMPT_CONTINUE;
+        if state.show_prompt {
+            prompt = PROMPT_CONTINUE.to_string();
+        }
     }
     Ok( input_buffer )
 }// }}}
 // }}}
 /// The public interface - turn command input into output string
-pub fn command_output( _full_stdin: &str ) -> String {// {{{
-    let command: String;
-    let arguments: Vec<String>;
-    match compose_command( _full_stdin ) {
-        ( cmd, args ) => {
-            command = cmd;
-            arguments = args;
-        },
-    }
-    let output: Output;
-    if arguments[0].len() == 0 {
-        output = Command::new( &command )
-                .output().expect("command failed");
-    } else {
-        output = Command::new( &command ).args( &arguments )
-                .output().expect("command failed");
-    }
-    let output_stdout = output.stdout;
-    // convert to RedError type
-    String::from_utf8( output_stdout )
-                        .expect("Failed to get output")
+///
+/// Failure to run the command is returned as RedError::CommandRun
+pub fn command_output( _full_stdin: &str ) -> Result<String, RedError> {// {{{
+    let ( command, arguments ) = try!( compose_command( _full_stdin ));
+    let output: Output = try!( Command::new( &command ).args( &arguments )
+            .output().map_err( |e| RedError::CommandRun(e) ));
+    Ok( decode( &output.stdout ))
 }// }}}
-/// Turn command-line input into std::process::Command object
-fn compose_command( _full_stdin: &str ) -> ( String, Vec<String> ) {// {{{
-    let arguments: Vec<String>;
-
-    match split_cmd_args( _full_stdin ) {
-        ( cmd, arg ) => {
-            arguments = split_args( &arg );
-            ( cmd, arguments )
-        },
-    }
+/// Run command with input fed to its stdin; return its stdout// {{{
+///
+/// Used for shell escapes and filtering lines through a command
+pub fn command_filter( _full_stdin: &str, input: &str )// {{{
+        -> Result<String, RedError> {
+    let ( command, arguments ) = try!( compose_command( _full_stdin ));
+    let mut process = Command::new( &command );
+    process.args( &arguments );
+    run_filter( process, input )
 }// }}}
-/// Split-off executed program/command from beginning of input
+// }}}
+/// Run program with arguments as given, input fed to its stdin// {{{
 ///
-/// Splits input into "<command> <arguments>" string
-/// returns <command> and <arguments> as separate strings for
-/// further processing
-fn split_cmd_args( _full_stdin: &str ) -> ( String, String ) {// {{{
-    let input = _full_stdin.trim();
-    let mut arguments = String::new();
-    let command: String;
-    let first_space = input.find( char::is_whitespace );
-    // TODO handle possible quoting? e.g.for paths with spaces
-    match first_space {
-        Some(x) => {
-            match input.split_at( x ) {
-                (zi, zf) => {
-                    command = zi.trim().to_string();
-                    arguments = zf.trim().to_string();
-                },
-            }
-        },
-        None => {
-            command = input.trim().to_string();
-        },
-    }
-    ( command, arguments )
+/// Unlike command_filter, arguments are not split on whitespace, so they
+/// may hold file names with spaces
+pub fn program_filter( program: &str, arguments: &[&str], input: &str )// {{{
+        -> Result<String, RedError> {
+    let mut process = Command::new( program );
+    process.args( arguments );
+    run_filter( process, input )
 }// }}}
-/// Convert string of arguments into vector
-fn split_args( stringed: &str ) -> Vec<String> {// {{{
-    let mut input = stringed.trim();
-    let mut argument = String::new();
+// }}}
+/// Spawn process, feed input to its stdin, and return its stdout// {{{
+fn run_filter( mut process: Command, input: &str )// {{{
+        -> Result<String, RedError> {
+    let mut child = try!( process.stdin( Stdio::piped() )
+                          .stdout( Stdio::piped() )
+                          .spawn()
+                          .map_err( |e| RedError::CommandRun(e) ));
+    let mut child_stdin = child.stdin.take()
+        .expect( "run_filter: child stdin not captured" );
+    let to_write = encode( input );
+    // write from separate thread so a chatty child can't block us
+    let writer = thread::spawn( move || {
+        let _ = child_stdin.write_all( &to_write );
+    });
+    let output = try!( child.wait_with_output()
+                       .map_err( |e| RedError::CommandRun(e) ));
+    let _ = writer.join();
+    Ok( decode( &output.stdout ))
+}// }}}
+// }}}
+/// Split command line into program and its arguments// {{{
+///
+/// A command line that can't be split, for an unterminated quote, is
+/// returned as RedError::CommandRun, just as a command that can't be run
+fn compose_command( _full_stdin: &str )// {{{
+        -> Result<( String, Vec<String> ), RedError> {
+    let mut arguments = try!( split_args( _full_stdin ).map_err( |e|
+            RedError::CommandRun( io::Error::new( io::ErrorKind::InvalidInput,
+                                                  e ))));
+    let command = if arguments.is_empty() {
+        String::new()
+    } else {
+        arguments.remove( 0 )
+    };
+    Ok( ( command, arguments ))
+}// }}}
+// }}}
+/// Split string into words, as a shell would// {{{
+///
+/// Words are separated by whitespace, which a word can hold when quoted
+/// with '...' or "..." or escaped with a backslash; quotes are removed.
+/// Inside double quotes a backslash escapes `"` and `\`; outside quotes it
+/// escapes whitespace, quotes, and `\`. Any other backslash is kept, so
+/// `echo -e one\ntwo` passes `one\ntwo` along
+fn split_args( stringed: &str ) -> Result<Vec<String>, &'static str> {// {{{
     let mut arguments: Vec<String> = Vec::new();
-    loop {
-        let next_space = input.trim().find( char::is_whitespace );
-        //let next_space = input.trim().find( " " );
-        match next_space {
-            Some(x) => {
-                match input.split_at( x ) {
-                    (zi, zf) => {
-                            input = zf.trim();
-                            argument = argument + zi.trim();
-                        if !is_quoted( stringed, x ) {
-                            arguments.push( argument );
-                            argument = String::new();
-                        }
+    let mut argument = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = stringed.chars().peekable();
+    while let Some( ch ) = chars.next() {
+        match ( quote, ch ) {
+            ( Some( q ), x ) if x == q => quote = None,
+            ( Some( '"' ), '\\' ) => {
+                match chars.peek() {
+                    Some( &x ) if x == '"' || x == '\\' => {
+                        argument.push( x );
+                        chars.next();
                     },
+                    _ => argument.push( '\\' ),
                 }
             },
-            None => {
-                assert!( argument.is_empty(),
-                        "command_output: unterminated quote" );
-                arguments.push( input.to_string() );
-                break;
+            ( Some( _ ), x ) => argument.push( x ),
+            ( None, '\'' ) | ( None, '"' ) => {
+                quote = Some( ch );
+                in_word = true;
+            },
+            ( None, '\\' ) => {
+                match chars.peek() {
+                    Some( &x ) if x.is_whitespace() || x == '\'' ||
+                            x == '"' || x == '\\' => {
+                        argument.push( x );
+                        chars.next();
+                    },
+                    _ => argument.push( '\\' ),
+                }
+                in_word = true;
+            },
+            ( None, x ) if x.is_whitespace() => {
+                if in_word {
+                    arguments.push( argument );
+                    argument = String::new();
+                    in_word = false;
+                }
+            },
+            ( None, x ) => {
+                argument.push( x );
+                in_word = true;
             },
         }
     }
-    arguments
+    if quote.is_some() {
+        return Err( "unterminated quote" );
+    }
+    if in_word {
+        arguments.push( argument );
+    }
+    Ok( arguments )
 }// }}}
+// }}}
 /// return true if character is quoted according to quot, bra, and ket
 ///
 /// quoted if preceded by odd number of "|'|`, or IMMEDIATELY preceded by
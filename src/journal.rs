@@ -0,0 +1,317 @@
+/*
+ * File   : journal.rs
+ * Purpose: append-only record of buffer changes for crash recovery
+ * Program: red
+ * About  : command-line text editor
+ * Authors: Tommy Lincoln <pajamapants3000@gmail.com>
+ * License: MIT; See LICENSE!
+ * Notes  : Notes on successful compilation
+ * Created: 10/17/2026
+ */
+
+//! Journal of buffer changes kept in the `.red.<name>` temp file
+//!
+//! The temp file starts with a snapshot of the buffer, followed by one
+//! record per line added or removed since the snapshot was taken. Records
+//! are collected in memory and appended to the file according to the
+//! FlushPolicy; once enough records pile up, the buffer writes a fresh
+//! snapshot instead (compaction).
+//!
+//! File format, one item per line:
+//!
+//! ```text
+//! RED-JOURNAL 1
+//! =<n>                    snapshot; the next n lines are buffer content
+//! +<address>\t<content>   line added, becoming line number <address>
+//! -<address>              line number <address> removed
+//! ```
+// *** Bring in to namespace *** {{{
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::fs::{self, File};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use io::*;
+use error::*;
+use lines::LineStore;
+// ^^^ Bring in to namespace ^^^ }}}
+
+// *** Constants *** {{{
+const JOURNAL_HEADER: &'static str = "RED-JOURNAL 1";
+/// file name prefix of temp files holding buffer journals
+pub const TEMP_FILE_PREFIX: &'static str = ".red.";
+/// compact once this many records, or one per buffer line, are on disk
+const COMPACT_MIN_RECORDS: usize = 1000;
+// ^^^ Constants ^^^ }}}
+
+// *** Data Structures *** {{{
+/// When pending journal records are written to disk// {{{
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlushPolicy {// {{{
+    /// after every command or line of input
+    EveryCommand,
+    /// after a command, if at least this many seconds since last flush
+    Timed( u64 ),
+    /// only when quitting with unsaved changes
+    OnQuit,
+}// }}}
+// }}}
+/// Records waiting to be appended to the temp file// {{{
+#[derive(Clone)]
+pub struct Journal {// {{{
+    /// serialized records not yet on disk
+    pending: String,
+    /// number of records in pending
+    num_pending: usize,
+    /// number of records on disk since the last snapshot
+    num_written: usize,
+    /// time of last snapshot or flush
+    last_flush: Instant,
+}// }}}
+// }}}
+impl Journal {// {{{
+    pub fn new() -> Journal {// {{{
+        Journal { pending: String::new(), num_pending: 0, num_written: 0,
+                  last_flush: Instant::now(), }
+    }// }}}
+    /// Record line added to buffer; mirrors Change::Add// {{{
+    pub fn record_add( &mut self, address: usize, content: &str ) {// {{{
+        self.pending.push_str( &format!( "+{}\t{}\n", address, content ));
+        self.num_pending += 1;
+    }// }}}
+    // }}}
+    /// Record line removed from buffer; mirrors Change::Remove// {{{
+    pub fn record_remove( &mut self, address: usize ) {// {{{
+        self.pending.push_str( &format!( "-{}\n", address ));
+        self.num_pending += 1;
+    }// }}}
+    // }}}
+    /// Return true if policy calls for a flush now// {{{
+    pub fn is_due( &self, policy: FlushPolicy ) -> bool {// {{{
+        if self.num_pending == 0 {
+            return false;
+        }
+        match policy {
+            FlushPolicy::EveryCommand => true,
+            FlushPolicy::Timed( seconds ) =>
+                self.last_flush.elapsed() >= Duration::from_secs( seconds ),
+            FlushPolicy::OnQuit => false,
+        }
+    }// }}}
+    // }}}
+    /// Return true if writing a snapshot beats appending more records// {{{
+    pub fn needs_compaction( &self, num_lines: usize ) -> bool {// {{{
+        let limit = if num_lines > COMPACT_MIN_RECORDS {
+            num_lines
+        } else {
+            COMPACT_MIN_RECORDS
+        };
+        self.num_written + self.num_pending > limit
+    }// }}}
+    // }}}
+    /// Append pending records to journal file// {{{
+    pub fn flush<S: AsRef<OsStr> + ?Sized>( &mut self, path: &S )// {{{
+            -> Result<(), RedError> {
+        if self.num_pending > 0 {
+            let file_mode = FileMode{ f_append: true, f_create: true,
+                    ..Default::default() };
+            let mut file_opened = try!( file_opener( path, file_mode ));
+            try!( file_opened.write_all( self.pending.as_bytes() )
+                  .map_err( |e| RedError::FileWrite(e) ));
+            self.num_written += self.num_pending;
+        }
+        self.pending.clear();
+        self.num_pending = 0;
+        self.last_flush = Instant::now();
+        Ok( () )
+    }// }}}
+    // }}}
+    /// Forget all records; a snapshot now holds everything// {{{
+    pub fn snapshot_written( &mut self ) {// {{{
+        self.pending.clear();
+        self.num_pending = 0;
+        self.num_written = 0;
+        self.last_flush = Instant::now();
+    }// }}}
+    // }}}
+}// }}}
+// ^^^ Data Structures ^^^ }}}
+
+// *** Functions *** {{{
+/// Write journal header and snapshot of lines// {{{
+pub fn write_snapshot<'a, I>( writer: &mut BufWriter<File>, num_lines: usize,// {{{
+                              lines: I ) -> Result<(), RedError>
+        where I: Iterator<Item=&'a String> {
+    try!( write!( writer, "{}\n={}\n", JOURNAL_HEADER, num_lines )
+          .map_err( |e| RedError::FileWrite(e) ));
+    for line in lines {
+        try!( writer.write_all( line.as_bytes() )
+              .map_err( |e| RedError::FileWrite(e) ));
+        try!( writer.write_all( b"\n" )
+              .map_err( |e| RedError::FileWrite(e) ));
+    }
+    Ok( () )
+}// }}}
+// }}}
+/// Rebuild buffer content from journal file// {{{
+///
+/// A file without the journal header is taken to be a plain copy of the
+/// buffer, as written by earlier versions. Replay stops at the first
+/// incomplete or unrecognized record, which is what a crash mid-write
+/// leaves behind.
+pub fn replay<S: AsRef<OsStr> + ?Sized>( path: &S )// {{{
+        -> Result<LineStore, RedError> {
+    let file_mode = FileMode{ f_read: true, ..Default::default() };
+    let mut file_opened = try!( file_opener( path, file_mode ));
+    let mut content = String::new();
+    try!( file_opened.read_to_string( &mut content )
+          .map_err( |e| RedError::FileRead(e) ));
+    // only complete lines count; a partial last line is a torn write
+    let complete_len = match content.rfind( '\n' ) {
+        Some( indx ) => indx + 1,
+        None => 0,
+    };
+    // lines() would also take a carriage return off the end of a record
+    let mut records: Vec<&str> = content[ .. complete_len ].split( '\n' )
+                                                          .collect();
+    // split leaves an empty piece after the last newline
+    records.pop();
+    let mut records = records.into_iter();
+    let mut lines: LineStore = LineStore::new();
+    match records.next() {
+        Some( JOURNAL_HEADER ) => {},
+        Some( first_line ) => {
+            lines.push( first_line.to_string() );
+            for line in records {
+                lines.push( line.to_string() );
+            }
+            return Ok( lines );
+        },
+        None => return Ok( lines ),
+    }
+    let num_lines: usize = match records.next() {
+        Some( x ) if x.starts_with( '=' ) => try!( x[1..].parse()
+            .map_err( |_| RedError::CriticalError(
+                    "journal: invalid snapshot size".to_string() ))),
+        _ => return Err( RedError::CriticalError(
+                    "journal: missing snapshot".to_string() )),
+    };
+    for _ in 0 .. num_lines {
+        match records.next() {
+            Some( line ) => lines.push( line.to_string() ),
+            None => return Ok( lines ),
+        }
+    }
+    for record in records {
+        if record.starts_with( '+' ) {
+            let ( address, line ) = match record[1..].find( '\t' ) {
+                Some( indx ) => ( &record[ 1 .. indx + 1 ],
+                                  &record[ indx + 2 .. ] ),
+                None => break,
+            };
+            match address.parse::<usize>() {
+                Ok( x ) if 0 < x && x <= lines.len() + 1 =>
+                    lines.insert( x - 1, line.to_string() ),
+                _ => break,
+            }
+        } else if record.starts_with( '-' ) {
+            match record[1..].parse::<usize>() {
+                Ok( x ) if 0 < x && x <= lines.len() => {
+                    lines.remove( x - 1 );
+                },
+                _ => break,
+            }
+        } else {
+            break;
+        }
+    }
+    Ok( lines )
+}// }}}
+// }}}
+/// List temp files in directory that could hold unsaved sessions// {{{
+pub fn list_sessions<P: AsRef<Path>>( dir: P )// {{{
+        -> Result<Vec<PathBuf>, RedError> {
+    let entries = try!( fs::read_dir( dir )
+                        .map_err( |e| RedError::FileRead(e) ));
+    let mut sessions: Vec<PathBuf> = Vec::new();
+    for entry in entries {
+        let entry = try!( entry.map_err( |e| RedError::FileRead(e) ));
+        let is_session = entry.file_name().to_str()
+            .map( |x| x.starts_with( TEMP_FILE_PREFIX ) &&
+                      x.len() > TEMP_FILE_PREFIX.len() )
+            .unwrap_or( false );
+        if is_session && entry.path().is_file() {
+            sessions.push( entry.path() );
+        }
+    }
+    sessions.sort();
+    Ok( sessions )
+}// }}}
+// }}}
+/// Return time since file was last modified// {{{
+pub fn file_age<P: AsRef<Path>>( path: P ) -> Option<Duration> {// {{{
+    fs::metadata( path ).ok()
+        .and_then( |x| x.modified().ok() )
+        .and_then( |x| x.elapsed().ok() )
+}// }}}
+// }}}
+/// Describe age in largest sensible unit, e.g. "3 minutes"// {{{
+pub fn format_age( age: Duration ) -> String {// {{{
+    let seconds = age.as_secs();
+    let ( amount, unit ) = if seconds < 60 {
+        ( seconds, "second" )
+    } else if seconds < 60 * 60 {
+        ( seconds / 60, "minute" )
+    } else if seconds < 24 * 60 * 60 {
+        ( seconds / ( 60 * 60 ), "hour" )
+    } else {
+        ( seconds / ( 24 * 60 * 60 ), "day" )
+    };
+    format!( "{} {}{}", amount, unit, if amount == 1 { "" } else { "s" } )
+}// }}}
+// }}}
+// ^^^ Functions ^^^ }}}
+
+#[cfg(test)]
+mod tests {// {{{
+    use std::{env, process};
+    use std::time::Duration;
+    use super::*;
+
+    /// Ages are given in largest whole unit// {{{
+    #[test]
+    fn format_age_test_1() {// {{{
+        assert_eq!( format_age( Duration::from_secs( 1 )), "1 second" );
+        assert_eq!( format_age( Duration::from_secs( 59 )), "59 seconds" );
+        assert_eq!( format_age( Duration::from_secs( 150 )), "2 minutes" );
+        assert_eq!( format_age( Duration::from_secs( 3600 )), "1 hour" );
+        assert_eq!( format_age( Duration::from_secs( 200000 )), "2 days" );
+    }// }}}
+    // }}}
+    /// Carriage returns at end of lines survive replay// {{{
+    #[test]
+    fn replay_test_1() {// {{{
+        let path = env::temp_dir().join( format!( "{}replay-{}",
+                TEMP_FILE_PREFIX, process::id() ));
+        let snapshot = vec!( "one\r".to_string(), "two".to_string() );
+        {
+            let mut writer = BufWriter::new( File::create( &path ).unwrap() );
+            write_snapshot( &mut writer, snapshot.len(), snapshot.iter() )
+                .unwrap();
+        }
+        let mut journal = Journal::new();
+        journal.record_add( 3, "three\r" );
+        journal.record_remove( 2 );
+        journal.flush( &path ).unwrap();
+        let lines = replay( &path ).unwrap();
+        assert!( lines.iter().eq( vec!( "one\r", "three\r" ).iter() ));
+        // plain copy, as written by earlier versions
+        fs::write( &path, "one\r\n\r\ntwo\n" ).unwrap();
+        let lines = replay( &path ).unwrap();
+        assert!( lines.iter().eq( vec!( "one\r", "\r", "two" ).iter() ));
+        fs::remove_file( &path ).unwrap();
+    }// }}}
+    // }}}
+}// }}}
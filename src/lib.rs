@@ -0,0 +1,738 @@
+/*
+ * File   : lib.rs
+ * Purpose: reimplementation of the classic `ed` in Rust, as a library
+ * Program: red
+ * About  : command-line text editor
+ * Authors: Tommy Lincoln <pajamapants3000@gmail.com>
+ * License: MIT; See LICENSE!
+ * Notes  : Notes on successful compilation
+ * Created: 10/16/2016
+ */
+
+//! A re-implementation, in Rust, of the classic `ed` program
+//!
+//! The editor is available to other programs through `Editor`, which
+//! loads text and runs ed commands against it:
+//!
+//! ```
+//! let mut editor = red::Editor::from_text( "one\ntwo\n" ).unwrap();
+//! editor.run( ",s/o/0/g\n$a\nthree\n.\n" ).unwrap();
+//! assert_eq!( editor.lines(), vec!( "0ne", "tw0", "three" ));
+//! editor.close();
+//! ```
+// Bring in to namespace {{{
+//extern crate clap;
+extern crate chrono;
+extern crate regex;
+extern crate rand;
+#[macro_use]
+extern crate lazy_static;
+extern crate term_size;
+
+mod io;
+mod parse;
+mod error;
+mod buf;
+mod ops;
+mod lines;
+mod journal;
+mod posix;
+mod editor;
+mod encoding;
+
+use std::fmt::{Debug, Display};
+use std::mem;
+use std::collections::VecDeque;
+
+use parse::*;
+use buf::*;
+//use error::*;
+pub use error::{RedError, error_message, exit_status};
+pub use editor::Editor;
+pub use io::{Terminal, StdTerminal, MemoryTerminal};
+#[cfg(feature = "bench")]
+pub use lines::LineStore;
+use journal::FlushPolicy;
+use posix::{RegexSyntax, translate};
+
+//use io::FileMode;
+
+// }}}
+// *** Constants *** {{{
+const DEFAULT_MODE: EditorMode = EditorMode::Command;
+const DEFAULT_HELP: bool = true;
+const DEFAULT_MESSAGES: bool = true;
+const DEFAULT_PROMPT: &'static str = "%";
+const DEFAULT_SHOW_PROMPT: bool = true;
+const DEFAULT_BATCH: bool = false;
+/// bytes of memory undo history may use before discarding oldest entries
+const DEFAULT_UNDO_LIMIT: usize = 64 * 1024 * 1024;
+const DEFAULT_FLUSH_POLICY: FlushPolicy = FlushPolicy::EveryCommand;
+const DEFAULT_REGEX_SYNTAX: RegexSyntax = RegexSyntax::Basic;
+// ^^^ Constants ^^^ }}}
+// *** Data Structures *** {{{
+/// Contain state values for the program during execution
+///
+/// TODO: include buffer and command structures?
+pub(crate) struct EditorState {
+    /// enum indicating current mode: Command (aka Normal) or Insert
+    mode: EditorMode,
+    /// whether to show or hide informational output
+    show_messages: bool,
+    /// whether to show or hide help, warnings, and error messages
+    show_help: bool,
+    /// whether to show prompts when collecting input
+    show_prompt: bool,
+    /// whether commands come from a script; first failure ends session
+    batch: bool,
+    /// whether last command was `q`, refused for unsaved changes
+    quit_warned: bool,
+    /// prompt shown in Command mode
+    prompt: String,
+    /// structure containing all text and plenty of logic for manipulating it
+    buffer: Buffer,
+    /// file name or command from which our initial text originates
+    source: String,
+    /// most recent help, warning, or error message
+    last_help: String,
+    /// last regex used in address search, substitution, or global command
+    last_regex: String,
+    /// replacement text of last substitution, if any
+    last_replacement: Option<String>,
+    /// which matches last substitution replaced
+    last_sub_which: WhichMatch,
+    /// whether regular expressions are typed as POSIX basic or extended
+    regex_syntax: RegexSyntax,
+    /// last command executed by the `!` operation
+    last_shell_command: String,
+    /// structure containing enough information to roll back latest change
+    undo: Undo,
+    /// source of input and destination of output
+    terminal: Box<dyn Terminal>,
+    /// when buffer changes are written to the temp file journal
+    flush_policy: FlushPolicy,
+    /// directory given to `-r`, whose recoverable sessions are to be listed
+    recovery_dir: Option<String>,
+}
+impl EditorState {
+    /// Initialize new editor state// {{{
+    ///
+    /// This will be used when program first loads, and any time we open
+    /// a new file or command using e.g. the edit operation
+    pub fn new( _buffer: Buffer ) -> EditorState {// {{{
+        EditorState { mode: DEFAULT_MODE, show_help: DEFAULT_HELP,
+            show_messages: DEFAULT_MESSAGES, prompt: DEFAULT_PROMPT.to_string(),
+            show_prompt: DEFAULT_SHOW_PROMPT, batch: DEFAULT_BATCH,
+            quit_warned: false,
+            buffer: _buffer, source: String::new(), last_help: String::new(),
+            last_regex: String::new(), last_replacement: None,
+            last_sub_which: WhichMatch::Number(1),
+            regex_syntax: DEFAULT_REGEX_SYNTAX,
+            last_shell_command: String::new(),
+            undo: Undo::new(), flush_policy: DEFAULT_FLUSH_POLICY,
+            terminal: Box::new( StdTerminal ), recovery_dir: None, }
+    }// }}}
+// }}}
+    /// Return regex to use, remembering it for next time// {{{
+    ///
+    /// An empty regex, as in `//`, `s//x/`, or `g//p`, means the last one
+    /// used by any search, substitution, or global command. The regex is
+    /// returned translated from POSIX syntax into regex crate syntax
+    pub fn use_regex( &mut self, re_str: &str ) -> Result<String, RedError> {// {{{
+        if re_str.is_empty() {
+            if self.last_regex.is_empty() {
+                return Err( RedError::NoPreviousRegex );
+            }
+            return Ok( translate( &self.last_regex, self.regex_syntax ));
+        }
+        let translated = translate( re_str, self.regex_syntax );
+        // check before remembering, so a typo doesn't replace a good regex
+        try!( compile_regex( &translated ));
+        self.last_regex = re_str.to_string();
+        Ok( translated )
+    }// }}}
+    // }}}
+    /// Replace buffer; undo history belonged to the old one, so it goes too// {{{
+    pub fn set_buffer( &mut self, buffer: Buffer ) {// {{{
+        self.buffer = buffer;
+        self.undo.clear();
+    }// }}}
+    // }}}
+    /// Reset undo - only need if we can't state.undo.reset( &state.buffer )
+    pub fn u_reset( &mut self ) {
+        self.undo.reset( &self.buffer )
+    }
+    /// Record line number of added line// {{{
+    pub fn u_added_line( &mut self, address: usize ) {// {{{
+        self.undo.added_lines( address, address )
+    }// }}}
+    // }}}
+    /// Record line number of added line// {{{
+    pub fn u_added_current_line( &mut self ) {// {{{
+        let current_address = self.buffer.get_current_address();
+        self.undo.added_lines( current_address, current_address )
+    }// }}}
+    // }}}
+    /// Record range of added lines// {{{
+    ///
+    /// # Panics
+    /// _initial and/or _final are not a valid address ( in range [1,$] )
+    pub fn u_added_lines( &mut self,// {{{
+                                      _initial: usize, _final: usize ) {
+        assert!( 0 < _initial && _initial <= self.buffer.num_lines() );
+        assert!( 0 < _final && _final <= self.buffer.num_lines() );
+        self.undo.added_lines( _initial, _final )
+    }// }}}
+    // }}}
+    /// Store current line, to be removed, in Undo structure// {{{
+    ///
+    /// do we use this?
+    pub fn u_deleting_current_line( &mut self ) {// {{{
+        let current_address = self.buffer.get_current_address();
+        self.undo.deleting_line( &self.buffer, current_address )
+    }// }}}
+    // }}}
+    /// Store provided line, to be removed, in Undo structure// {{{
+    pub fn u_deleting_line( &mut self, address: usize ) {// {{{
+        self.undo.deleting_line( &self.buffer, address )
+    }// }}}
+    // }}}
+    /// Store lines to be removed, with addresses, in Undo structure// {{{
+    ///
+    /// # Panics
+    /// _initial and/or _final are not a valid address ( in range [0,$] )
+    /// Confirmed address still returns None for buffer::get_line_content ( This
+    ///     should never be logically possible! )
+    pub fn u_deleting_lines( &mut self,// {{{
+                                       _initial: usize, _final: usize ) {
+        // can be 0: e.g. when inserting first line
+        assert!( _initial <= self.buffer.num_lines() );
+        assert!( _final <= self.buffer.num_lines() );
+        self.undo.deleting_lines( &self.buffer, _initial, _final )
+    }// }}}
+    // }}}
+    /// Lock undo structure//{{{
+    pub fn u_lock( &mut self ) {// {{{
+        self.undo.lock()
+    }// }}}
+    // }}}
+    /// Unlock undo structure//{{{
+    pub fn u_unlock( &mut self ) {// {{{
+        self.undo.unlock()
+    }// }}}
+    // }}}
+    /// Roll back most recent change; return false if nothing to undo//{{{
+    pub fn u_undo( &mut self ) -> Result<bool, RedError> {// {{{
+        match self.undo.pop_undo() {
+            Some( entry ) => {
+                let redo_entry = try!( entry.revert( &mut self.buffer ));
+                self.undo.push_redo( redo_entry );
+                Ok( true )
+            },
+            None => Ok( false ),
+        }
+    }// }}}
+    // }}}
+    /// Re-apply most recently undone change; false if nothing to redo//{{{
+    pub fn u_redo( &mut self ) -> Result<bool, RedError> {// {{{
+        match self.undo.pop_redo() {
+            Some( entry ) => {
+                let undo_entry = try!( entry.revert( &mut self.buffer ));
+                self.undo.push_undo( undo_entry );
+                Ok( true )
+            },
+            None => Ok( false ),
+        }
+    }// }}}
+    // }}}
+    /// Set maximum memory, in bytes, used by undo history; 0 for no limit
+    pub fn u_set_memory_limit( &mut self, limit: usize ) {// {{{
+        self.undo.set_memory_limit( limit )
+    }// }}}
+}
+#[derive(Clone)]
+pub(crate) enum EditorMode {
+    Command,
+    Insert,
+}
+/// Lines to add, lines to remove, to be used by the undo operation
+///
+/// Perhaps not the most space-efficient approach, but probably faster and
+/// definitely simple; Inspired by diff;
+/// Each command gets its own UndoEntry; entries are kept in history until
+/// undone, at which point they move to future so they may be redone.
+#[derive(Clone)]
+struct Undo {
+    /// entries that can be undone, oldest first
+    ///
+    /// The last entry is the one currently being recorded into
+    history: VecDeque<UndoEntry>,
+    /// entries that have been undone and can be redone, most recent last
+    future: Vec<UndoEntry>,
+    /// approximate memory, in bytes, used by history and future
+    memory_used: usize,
+    /// discard oldest entries when memory_used exceeds this; 0 for no limit
+    memory_limit: usize,
+    /// lock the structure for complex operations
+    is_locked: bool
+}
+impl Undo {
+    /// Begin recording a new entry, unless locked// {{{
+    ///
+    /// An entry that never recorded any changes is simply replaced
+    pub fn reset( &mut self, buffer: &Buffer ) {// {{{
+        if !self.is_locked {
+            let is_empty = match self.history.back() {
+                Some( entry ) => entry.changes.is_empty(),
+                None => false,
+            };
+            if is_empty {
+                let entry = self.history.pop_back()
+                    .expect( "lib::Undo::reset: no entry" );
+                self.memory_used -= entry.memory_size();
+            }
+            self.push_undo( UndoEntry::new( buffer ));
+        }
+    }// }}}
+    // }}}
+    /// Forget all entries, both those to undo and those to redo// {{{
+    pub fn clear( &mut self ) {// {{{
+        self.history.clear();
+        self.future.clear();
+        self.memory_used = 0;
+    }// }}}
+    // }}}
+    pub fn lock( &mut self ) {
+        self.is_locked = true;
+    }
+    pub fn unlock( &mut self ) {
+        self.is_locked = false;
+    }
+    /// Record range of added lines// {{{
+    ///
+    /// # Panics
+    /// _initial and/or _final are not a valid address ( in range [1,$] )
+    pub fn added_lines( &mut self,// {{{
+                                      _initial: usize, _final: usize ) {
+        for _address in _initial .. _final + 1 {
+            self.record( Change::Add{ address: _address });
+        }
+    }// }}}
+    // }}}
+    /// Store provided line, to be removed, in Undo structure// {{{
+    pub fn deleting_line( &mut self, buffer: &Buffer, address: usize ) {// {{{
+        self.deleting_lines( buffer, address, address )
+    }// }}}
+    // }}}
+    /// Store lines to be removed, with addresses, in Undo structure// {{{
+    ///
+    /// # Panics
+    /// _initial and/or _final are not a valid address ( in range [0,$] )
+    /// Confirmed address still returns None for buffer::get_line_content ( This
+    ///     should never be logically possible! )
+    pub fn deleting_lines( &mut self, buffer: &Buffer,// {{{
+                                       _initial: usize, _final: usize ) {
+        for _address in _initial .. _final + 1 {
+        // to delete range, delete same address repeatedly on _initial
+            self.record( Change::Remove{ address: _initial,
+        // however, not ACTUALLY deleting right now, so we have to increment
+        // to get the correct content to save - use _address
+                    content: buffer.get_line_content( _address )
+                    .expect( &( "lib::u_deleting_lines: ".to_string() +
+                                "unexpected missing line" ))
+                    .to_string() });
+        }
+    }// }}}
+    // }}}
+    /// Add change to entry being recorded; any undone entries are lost// {{{
+    fn record( &mut self, change: Change ) {// {{{
+        if self.history.is_empty() {
+            // nobody called reset; we have no address or markers to save
+            self.push_undo( UndoEntry{ changes: Vec::new(),
+                    wascurrent_address: 0, markers: vec!( 0; NUM_LC ), });
+        }
+        for entry in self.future.drain( .. ) {
+            self.memory_used -= entry.memory_size();
+        }
+        self.memory_used += change.memory_size();
+        self.history.back_mut().expect( "lib::Undo::record: no entry" )
+            .changes.push( change );
+        self.enforce_memory_limit();
+    }// }}}
+    // }}}
+    /// Remove and return most recent entry that can be undone// {{{
+    pub fn pop_undo( &mut self ) -> Option<UndoEntry> {// {{{
+        while let Some( entry ) = self.history.pop_back() {
+            self.memory_used -= entry.memory_size();
+            if !entry.changes.is_empty() {
+                return Some( entry );
+            }
+        }
+        None
+    }// }}}
+    // }}}
+    /// Remove and return most recently undone entry// {{{
+    pub fn pop_redo( &mut self ) -> Option<UndoEntry> {// {{{
+        self.future.pop().map( |entry| {
+            self.memory_used -= entry.memory_size();
+            entry
+        })
+    }// }}}
+    // }}}
+    /// Save entry that will reverse an undo// {{{
+    pub fn push_redo( &mut self, entry: UndoEntry ) {// {{{
+        self.memory_used += entry.memory_size();
+        self.future.push( entry );
+        self.enforce_memory_limit();
+    }// }}}
+    // }}}
+    /// Push entry onto history, leaving future intact// {{{
+    pub fn push_undo( &mut self, entry: UndoEntry ) {// {{{
+        self.memory_used += entry.memory_size();
+        self.history.push_back( entry );
+        self.enforce_memory_limit();
+    }// }}}
+    // }}}
+    /// Set memory limit and discard entries as needed to meet it// {{{
+    pub fn set_memory_limit( &mut self, limit: usize ) {// {{{
+        self.memory_limit = limit;
+        self.enforce_memory_limit();
+    }// }}}
+    // }}}
+    /// Discard oldest history, then furthest future, until under limit// {{{
+    ///
+    /// The most recent history entry is never discarded, so the command
+    /// currently being recorded can always be undone
+    fn enforce_memory_limit( &mut self ) {// {{{
+        if self.memory_limit == 0 {
+            return;
+        }
+        while self.memory_used > self.memory_limit && self.history.len() > 1 {
+            let entry = self.history.pop_front()
+                .expect( "lib::Undo::enforce_memory_limit: no entry" );
+            self.memory_used -= entry.memory_size();
+        }
+        while self.memory_used > self.memory_limit && !self.future.is_empty() {
+            let entry = self.future.remove( 0 );
+            self.memory_used -= entry.memory_size();
+        }
+    }// }}}
+    // }}}
+    fn new() -> Undo {
+        Undo {
+            history: VecDeque::new(),
+            future: Vec::new(),
+            memory_used: 0,
+            memory_limit: DEFAULT_UNDO_LIMIT,
+            is_locked: false,
+        }
+    }
+}
+/// Everything needed to roll back a single command// {{{
+#[derive(Clone)]
+struct UndoEntry {
+    /// Vector of changes
+    ///
+    /// Each change is pushed onto the stack, then if we execute undo
+    /// we pop the changes off one-by-one, rolling back step-by-step
+    ///
+    /// Each step is either an added line - just an address, or a
+    /// removed line - an address and the full line content.
+    /// When executing undo, all added lines are simply deleted, and
+    /// removed lines are re-inserted.
+    changes: Vec<Change>,
+    /// current address before the change
+    wascurrent_address: usize,
+    /// markers before the change
+    markers: Vec<usize>,
+}
+impl UndoEntry {
+    /// Start empty entry, saving buffer's current address and markers// {{{
+    fn new( buffer: &Buffer ) -> UndoEntry {// {{{
+        UndoEntry {
+            changes: Vec::new(),
+            wascurrent_address: buffer.get_current_address(),
+            markers: get_markers( buffer ),
+        }
+    }// }}}
+    // }}}
+    /// Roll back changes in buffer; return entry that reverses this one// {{{
+    fn revert( mut self, buffer: &mut Buffer )// {{{
+            -> Result<UndoEntry, RedError> {
+        let mut reverse = UndoEntry::new( buffer );
+        loop {
+            match self.changes.pop() {
+                Some( Change::Add{ address: _address }) => {
+                    reverse.changes.push( Change::Remove{ address: _address,
+                        content: buffer.get_line_content( _address )
+                            .unwrap_or( "" ).to_string() });
+                    try!( buffer.delete_line( _address ) );
+                },
+                Some( Change::Remove{ address: _address,
+                                      content: _content }) => {
+                    reverse.changes.push( Change::Add{ address: _address });
+                    buffer.append_line( _address - 1, &_content );
+                },
+                None => break,
+            }
+        }
+        buffer.set_current_address( self.wascurrent_address );
+        for indx in ( 'a' as u8 ) .. ( 'z' as u8 ) + 1 {
+            buffer.set_marker( indx as char,
+                self.markers[ ( indx as usize ) - ( 'a' as usize ) ] );
+        }
+        Ok( reverse )
+    }// }}}
+    // }}}
+    /// Approximate memory, in bytes, used by entry// {{{
+    fn memory_size( &self ) -> usize {// {{{
+        let mut size = mem::size_of::<UndoEntry>() +
+            self.markers.len() * mem::size_of::<usize>();
+        for change in &self.changes {
+            size += change.memory_size();
+        }
+        size
+    }// }}}
+    // }}}
+}
+// }}}
+#[derive(Clone)]
+pub(crate) enum Change {
+    Add { address: usize },
+    Remove { address: usize, content: String },
+}
+impl Change {
+    /// Approximate memory, in bytes, used by change// {{{
+    fn memory_size( &self ) -> usize {// {{{
+        match *self {
+            Change::Add{ .. } => mem::size_of::<Change>(),
+            Change::Remove{ ref content, .. } =>
+                mem::size_of::<Change>() + content.len(),
+        }
+    }// }}}
+    // }}}
+}
+/// Return snapshot of all markers in buffer// {{{
+fn get_markers( buffer: &Buffer ) -> Vec<usize> {// {{{
+    let mut _markers = vec!( 0; NUM_LC );
+    for indx in ( 'a' as u8 ) .. ( 'z' as u8 ) + 1 {
+        _markers[ ( indx as usize ) - ( 'a' as usize ) ] =
+            buffer.get_marked_line( indx as char );
+    }
+    _markers
+}// }}}
+// }}}
+// ^^^ Data Structures ^^^ }}}
+
+// *** Functions *** {{{
+/// Print standard messages
+///
+/// TODO: Change first arg to just boolean: state.help?
+pub(crate) fn print_msg<T: Display>( state: &mut EditorState, output: T ) {// {{{
+    if state.show_messages {
+        print_line( state, output );
+    }
+}// }}}
+
+/// Print help, warnings, other output depending on setting
+///
+/// TODO: Change first arg to just boolean: state.help?
+pub(crate) fn print_help<T: Display>( state: &mut EditorState, output: T ) {// {{{
+    if state.show_help {
+        print_line( state, output );
+    } else {
+        print_line( state, "?" );
+    }
+}// }}}
+
+/// Report failed command; explanation is kept for the `h` operation
+pub(crate) fn print_error( state: &mut EditorState, error: RedError ) {// {{{
+    state.last_help = error_message( &error );
+    let message = state.last_help.clone();
+    print_help( state, message );
+}// }}}
+
+/// Print help, warnings, other output depending on setting
+///
+/// TODO: Change first arg to just boolean: state.help?
+pub(crate) fn print_help_debug<T: Debug>( state: &mut EditorState, output: T ) {// {{{
+    if state.show_help {
+        print_line( state, format!( "{:?}", output ));
+    } else {
+        print_line( state, "?" );
+    }
+}// }}}
+
+/// Write line of output to terminal
+///
+/// # Panics
+/// if terminal fails to write, as println! would
+pub(crate) fn print_line<T: Display>( state: &mut EditorState, output: T ) {// {{{
+    state.terminal.write( &format!( "{}\n", output ))
+        .expect( "print_line: failed to write output" );
+}// }}}
+
+// ^^^ Functions ^^^ }}}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ops::Operations;
+
+    /// Prep editor state with three-line buffer, output kept in memory// {{{
+    fn open_undo_test( test_num: u8 ) -> EditorState {// {{{
+        let mut buffer = Buffer::new( BufferInput::Command(
+                "echo -e one\\ntwo\\nthree".to_string() )).unwrap();
+        buffer.move_file( &( "red_undotest".to_string() +
+                             test_num.to_string().as_str() )).unwrap();
+        let mut state = EditorState::new( buffer );
+        state.terminal = Box::new( MemoryTerminal::default() );
+        state
+    }// }}}
+    // }}}
+    /// Delete a line, recording it as a single command// {{{
+    fn delete_line( state: &mut EditorState, address: usize ) {// {{{
+        state.u_reset();
+        state.u_lock();
+        state.u_deleting_line( address );
+        state.buffer.delete_line( address ).unwrap();
+        state.u_unlock();
+    }// }}}
+    // }}}
+    /// Step back through several commands, then forward again// {{{
+    #[test]
+    fn undo_test_1() {// {{{
+        let mut state = open_undo_test( 1 );
+        delete_line( &mut state, 1 );
+        delete_line( &mut state, 1 );
+        assert_eq!( state.buffer.num_lines(), 1 );
+        assert!( state.u_undo().unwrap() );
+        assert_eq!( state.buffer.get_line_content( 1 ).unwrap(), "two" );
+        assert!( state.u_undo().unwrap() );
+        assert_eq!( state.buffer.get_line_content( 1 ).unwrap(), "one" );
+        assert_eq!( state.buffer.num_lines(), 3 );
+        assert!( !state.u_undo().unwrap() );
+        assert!( state.u_redo().unwrap() );
+        assert!( state.u_redo().unwrap() );
+        assert!( !state.u_redo().unwrap() );
+        assert_eq!( state.buffer.get_line_content( 1 ).unwrap(), "three" );
+        state.buffer.destruct();
+    }// }}}
+    // }}}
+    /// New change discards redo; memory limit discards oldest entries// {{{
+    #[test]
+    fn undo_test_2() {// {{{
+        let mut state = open_undo_test( 2 );
+        delete_line( &mut state, 1 );
+        assert!( state.u_undo().unwrap() );
+        delete_line( &mut state, 3 );
+        assert!( !state.u_redo().unwrap() );
+        state.u_set_memory_limit( 1 );
+        delete_line( &mut state, 1 );
+        assert!( state.u_undo().unwrap() );
+        assert!( !state.u_undo().unwrap() );
+        assert_eq!( state.buffer.num_lines(), 2 );
+        state.buffer.destruct();
+    }// }}}
+    // }}}
+    /// Global command is undone in a single step// {{{
+    #[test]
+    fn undo_test_3() {// {{{
+        let operations = Operations::new();
+        let mut state = open_undo_test( 3 );
+        state.buffer.set_current_address( 2 );
+        {
+            let command = parse_command( "g/o/s/o/0/", &mut state,
+                                         &operations ).unwrap();
+            operations.execute( &mut state, command ).unwrap();
+        }
+        state.u_unlock();
+        assert_eq!( state.buffer.get_line_content( 1 ).unwrap(), "0ne" );
+        assert_eq!( state.buffer.get_line_content( 2 ).unwrap(), "tw0" );
+        assert!( state.u_undo().unwrap() );
+        assert_eq!( state.buffer.get_line_content( 1 ).unwrap(), "one" );
+        assert_eq!( state.buffer.get_line_content( 2 ).unwrap(), "two" );
+        assert_eq!( state.buffer.get_current_address(), 2 );
+        assert!( !state.u_undo().unwrap() );
+        state.buffer.destruct();
+    }// }}}
+    // }}}
+    /// Parse and execute command, as main loop does// {{{
+    fn run( state: &mut EditorState, operations: &Operations,// {{{
+            input: &str ) -> Result<(), RedError> {
+        let command = try!( parse_command( input, state, operations ));
+        operations.execute( state, command )
+    }// }}}
+    // }}}
+    /// Searches, substitutions, and global commands share last regex// {{{
+    #[test]
+    fn last_regex_test_1() {// {{{
+        let operations = Operations::new();
+        let mut state = open_undo_test( 4 );
+        match run( &mut state, &operations, "s//x/" ) {
+            Err( RedError::NoPreviousRegex ) => {},
+            _ => panic!( "expected NoPreviousRegex" ),
+        }
+        match run( &mut state, &operations, "s/e/%/" ) {
+            Err( RedError::NoPreviousSubstitution ) => {},
+            _ => panic!( "expected NoPreviousSubstitution" ),
+        }
+        run( &mut state, &operations, "/hr/s//HR/" ).unwrap();
+        assert_eq!( state.buffer.get_line_content( 3 ).unwrap(), "tHRee" );
+        run( &mut state, &operations, "1s/o/0/" ).unwrap();
+        run( &mut state, &operations, "2s" ).unwrap();
+        assert_eq!( state.buffer.get_line_content( 2 ).unwrap(), "tw0" );
+        run( &mut state, &operations, "1s/n/%/" ).unwrap();
+        assert_eq!( state.buffer.get_line_content( 1 ).unwrap(), "00e" );
+        run( &mut state, &operations, "1s/e/E/" ).unwrap();
+        run( &mut state, &operations, "g//s//%%/" ).unwrap();
+        assert_eq!( state.buffer.get_line_content( 1 ).unwrap(), "00E" );
+        assert_eq!( state.buffer.get_line_content( 3 ).unwrap(), "tHR%%e" );
+        run( &mut state, &operations, "3sg" ).unwrap();
+        assert_eq!( state.buffer.get_line_content( 3 ).unwrap(), "tHR%%%%" );
+        state.buffer.destruct();
+    }// }}}
+    // }}}
+    /// Bad regex is reported and leaves buffer and last regex alone// {{{
+    #[test]
+    fn regex_error_test_1() {// {{{
+        let operations = Operations::new();
+        let terminal = MemoryTerminal::default();
+        let mut state = open_undo_test( 5 );
+        state.terminal = Box::new( terminal.clone() );
+        run( &mut state, &operations, "/t/=" ).unwrap();
+        assert_eq!( terminal.take_output(), "3\n" );
+        for input in &[ "/foo\\(/p", "1,3s/foo\\(/x/", "g/a\\(/d",
+                        "1,3s/a\\)/x/" ] {
+            match run( &mut state, &operations, input ) {
+                Err( RedError::Regex(_) ) => {},
+                _ => panic!( "expected Regex error for {}", input ),
+            }
+            state.u_unlock();
+        }
+        assert!( state.buffer.lines_iterator()
+                 .eq( [ "one", "two", "three" ].iter() ));
+        assert_eq!( state.last_regex, "t" );
+        assert!( !state.u_undo().unwrap() );
+        state.buffer.destruct();
+    }// }}}
+    // }}}
+    /// Newline in replacement splits lines, undone as one command// {{{
+    #[test]
+    fn split_line_test_1() {// {{{
+        let operations = Operations::new();
+        let mut state = open_undo_test( 6 );
+        state.buffer.set_marker( 'a', 3 );
+        run( &mut state, &operations, "1,2s/o/-\n-/" ).unwrap();
+        state.u_unlock();
+        assert!( state.buffer.lines_iterator()
+                 .eq( [ "-", "-ne", "tw-", "-", "three" ].iter() ));
+        assert_eq!( state.buffer.get_marked_line( 'a' ), 5 );
+        assert_eq!( state.buffer.get_current_address(), 4 );
+        assert!( state.u_undo().unwrap() );
+        assert!( state.buffer.lines_iterator()
+                 .eq( [ "one", "two", "three" ].iter() ));
+        state.buffer.destruct();
+    }// }}}
+    // }}}
+}
+
@@ -0,0 +1,302 @@
+/*
+ * File   : lines.rs
+ * Purpose: indexed storage for the lines of a buffer
+ * Program: red
+ * About  : command-line text editor
+ * Authors: Tommy Lincoln <pajamapants3000@gmail.com>
+ * License: MIT; See LICENSE!
+ * Notes  : Notes on successful compilation
+ * Created: 10/17/2026
+ */
+
+//! Gap buffer of lines
+//!
+//! Lines are kept in a single vector with a gap of unused slots at the
+//! position of the most recent edit. Access by index is O(1); inserting or
+//! removing at the gap is O(1), and moving the gap costs only the distance
+//! moved, so runs of nearby edits (appending input, global commands) stay
+//! cheap even in very large files.
+// *** Bring in to namespace *** {{{
+use std::cmp;
+use std::iter::{FromIterator, Iterator, DoubleEndedIterator, ExactSizeIterator};
+use std::mem;
+// ^^^ Bring in to namespace ^^^ }}}
+
+// *** Constants *** {{{
+/// smallest number of slots added when the gap is exhausted
+const MIN_GAP: usize = 64;
+// ^^^ Constants ^^^ }}}
+
+// *** Data Structures *** {{{
+/// Collection of lines with constant-time access by index// {{{
+///
+/// Indices are zero-based; Buffer handles translation from addresses
+#[derive(Clone)]
+pub struct LineStore {// {{{
+    /// lines before the gap, the gap itself (empty strings), lines after
+    lines: Vec<String>,
+    /// index of first slot in gap
+    gap_start: usize,
+    /// index of first slot after gap
+    gap_end: usize,
+}// }}}
+// }}}
+impl LineStore {// {{{
+    /// Create empty store// {{{
+    pub fn new() -> LineStore {// {{{
+        LineStore { lines: Vec::new(), gap_start: 0, gap_end: 0 }
+    }// }}}
+    // }}}
+    /// Return number of lines stored// {{{
+    pub fn len( &self ) -> usize {// {{{
+        self.lines.len() - self.gap_len()
+    }// }}}
+    // }}}
+    /// Return true if no lines are stored// {{{
+    pub fn is_empty( &self ) -> bool {// {{{
+        self.len() == 0
+    }// }}}
+    // }}}
+    /// Return line at index, if it exists// {{{
+    pub fn get( &self, index: usize ) -> Option<&String> {// {{{
+        if index >= self.len() {
+            None
+        } else {
+            Some( &self.lines[ self.raw_index( index ) ] )
+        }
+    }// }}}
+    // }}}
+    /// Replace line at index, returning the old line// {{{
+    ///
+    /// # Panics
+    /// index is not less than len()
+    pub fn set( &mut self, index: usize, line: String ) -> String {// {{{
+        assert!( index < self.len(), "LineStore::set: index out of range" );
+        let raw = self.raw_index( index );
+        mem::replace( &mut self.lines[ raw ], line )
+    }// }}}
+    // }}}
+    /// Insert line so that it ends up at index// {{{
+    ///
+    /// # Panics
+    /// index is greater than len()
+    pub fn insert( &mut self, index: usize, line: String ) {// {{{
+        assert!( index <= self.len(), "LineStore::insert: index out of range" );
+        if self.gap_len() == 0 {
+            self.grow_gap();
+        }
+        self.move_gap( index );
+        self.lines[ self.gap_start ] = line;
+        self.gap_start += 1;
+    }// }}}
+    // }}}
+    /// Remove and return line at index// {{{
+    ///
+    /// # Panics
+    /// index is not less than len()
+    pub fn remove( &mut self, index: usize ) -> String {// {{{
+        assert!( index < self.len(), "LineStore::remove: index out of range" );
+        self.move_gap( index );
+        let line = mem::replace( &mut self.lines[ self.gap_end ],
+                                 String::new() );
+        self.gap_end += 1;
+        line
+    }// }}}
+    // }}}
+    /// Append line to end of store// {{{
+    pub fn push( &mut self, line: String ) {// {{{
+        let index = self.len();
+        self.insert( index, line );
+    }// }}}
+    // }}}
+    /// Remove all lines// {{{
+    pub fn clear( &mut self ) {// {{{
+        self.lines.clear();
+        self.gap_start = 0;
+        self.gap_end = 0;
+    }// }}}
+    // }}}
+    /// Return iterator over all lines// {{{
+    pub fn iter<'a>( &'a self ) -> Lines<'a> {// {{{
+        Lines { store: self, front: 0, back: self.len() }
+    }// }}}
+    // }}}
+    /// Return iterator over lines in [start, end)// {{{
+    ///
+    /// # Panics
+    /// start > end, or end > len()
+    pub fn range<'a>( &'a self, start: usize, end: usize ) -> Lines<'a> {// {{{
+        assert!( start <= end && end <= self.len(),
+                 "LineStore::range: invalid range" );
+        Lines { store: self, front: start, back: end }
+    }// }}}
+    // }}}
+    fn gap_len( &self ) -> usize {// {{{
+        self.gap_end - self.gap_start
+    }// }}}
+    /// Translate line index into index of lines vector// {{{
+    fn raw_index( &self, index: usize ) -> usize {// {{{
+        if index < self.gap_start {
+            index
+        } else {
+            index + self.gap_len()
+        }
+    }// }}}
+    // }}}
+    /// Shift lines across the gap so that it begins at index// {{{
+    ///
+    /// Lines are swapped with the empty slots a gap-sized chunk at a time
+    fn move_gap( &mut self, index: usize ) {// {{{
+        let gap_len = self.gap_len();
+        if gap_len == 0 {
+            self.gap_start = index;
+            self.gap_end = index;
+            return;
+        }
+        while index < self.gap_start {
+            let chunk = cmp::min( gap_len, self.gap_start - index );
+            {
+                let ( left, right ) =
+                    self.lines.split_at_mut( self.gap_end - chunk );
+                left[ self.gap_start - chunk .. self.gap_start ]
+                    .swap_with_slice( &mut right[ .. chunk ] );
+            }
+            self.gap_start -= chunk;
+            self.gap_end -= chunk;
+        }
+        while index > self.gap_start {
+            let chunk = cmp::min( gap_len, index - self.gap_start );
+            {
+                let ( left, right ) = self.lines.split_at_mut( self.gap_end );
+                left[ self.gap_start .. self.gap_start + chunk ]
+                    .swap_with_slice( &mut right[ .. chunk ] );
+            }
+            self.gap_start += chunk;
+            self.gap_end += chunk;
+        }
+    }// }}}
+    // }}}
+    /// Enlarge gap in proportion to number of lines stored// {{{
+    fn grow_gap( &mut self ) {// {{{
+        let extra = cmp::max( MIN_GAP, self.len() );
+        let gap_end = self.gap_end;
+        self.lines.splice( gap_end .. gap_end,
+                           ( 0 .. extra ).map( |_| String::new() ));
+        self.gap_end += extra;
+    }// }}}
+    // }}}
+}// }}}
+impl FromIterator<String> for LineStore {// {{{
+    fn from_iter<I: IntoIterator<Item=String>>( iter: I ) -> Self {// {{{
+        let lines: Vec<String> = iter.into_iter().collect();
+        let len = lines.len();
+        LineStore { lines: lines, gap_start: len, gap_end: len }
+    }// }}}
+}// }}}
+/// Iterator over a range of lines in a LineStore// {{{
+pub struct Lines<'a> {// {{{
+    store: &'a LineStore,
+    /// index of next line from the front
+    front: usize,
+    /// one past index of next line from the back
+    back: usize,
+}// }}}
+// }}}
+impl<'a> Iterator for Lines<'a> {// {{{
+    type Item = &'a String;
+    fn next( &mut self ) -> Option<&'a String> {// {{{
+        if self.front >= self.back {
+            return None;
+        }
+        let line = self.store.get( self.front );
+        self.front += 1;
+        line
+    }// }}}
+    fn size_hint( &self ) -> ( usize, Option<usize> ) {// {{{
+        let remaining = self.back - self.front;
+        ( remaining, Some( remaining ))
+    }// }}}
+}// }}}
+impl<'a> DoubleEndedIterator for Lines<'a> {// {{{
+    fn next_back( &mut self ) -> Option<&'a String> {// {{{
+        if self.front >= self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.store.get( self.back )
+    }// }}}
+}// }}}
+impl<'a> ExactSizeIterator for Lines<'a> {}
+// ^^^ Data Structures ^^^ }}}
+
+#[cfg(test)]
+mod tests {// {{{
+    use super::*;
+
+    /// Build store of numbered lines// {{{
+    fn numbered( num_lines: usize ) -> LineStore {// {{{
+        ( 0 .. num_lines ).map( |x| x.to_string() ).collect()
+    }// }}}
+    // }}}
+    /// Insert and remove scattered around the store// {{{
+    #[test]
+    fn line_store_test_1() {// {{{
+        let mut store = numbered( 10 );
+        let mut expectation: Vec<String> =
+            ( 0 .. 10 ).map( |x| x.to_string() ).collect();
+        store.insert( 3, "a".to_string() );
+        expectation.insert( 3, "a".to_string() );
+        store.insert( 0, "b".to_string() );
+        expectation.insert( 0, "b".to_string() );
+        assert_eq!( store.remove( 7 ), expectation.remove( 7 ));
+        store.push( "c".to_string() );
+        expectation.push( "c".to_string() );
+        assert_eq!( store.set( 5, "d".to_string() ), expectation[5] );
+        expectation[5] = "d".to_string();
+        assert_eq!( store.len(), expectation.len() );
+        for indx in 0 .. expectation.len() {
+            assert_eq!( store.get( indx ), Some( &expectation[ indx ] ));
+        }
+        assert_eq!( store.get( expectation.len() ), None );
+        assert!( store.iter().eq( expectation.iter() ));
+        assert!( store.iter().rev().eq( expectation.iter().rev() ));
+    }// }}}
+    // }}}
+    /// Ranges iterate from both ends// {{{
+    #[test]
+    fn line_store_test_2() {// {{{
+        let mut store = numbered( 200 );
+        store.insert( 100, "x".to_string() );
+        let mut range = store.range( 98, 103 );
+        assert_eq!( range.len(), 5 );
+        assert_eq!( range.next().unwrap(), "98" );
+        assert_eq!( range.next_back().unwrap(), "101" );
+        let rest: Vec<&String> = range.collect();
+        assert_eq!( rest, vec!( "99", "x", "100" ));
+        store.clear();
+        assert!( store.is_empty() );
+        assert_eq!( store.iter().next(), None );
+    }// }}}
+    // }}}
+    /// Scattered edits agree with a plain vector// {{{
+    #[test]
+    fn line_store_test_3() {// {{{
+        let mut store = numbered( 300 );
+        let mut expectation: Vec<String> =
+            ( 0 .. 300 ).map( |x| x.to_string() ).collect();
+        let mut seed: usize = 7;
+        for step in 0 .. 2000 {
+            seed = ( seed * 1103515245 + 12345 ) % 2147483648;
+            if step % 3 == 0 {
+                let indx = seed % expectation.len();
+                assert_eq!( store.remove( indx ), expectation.remove( indx ));
+            } else {
+                let indx = seed % ( expectation.len() + 1 );
+                store.insert( indx, step.to_string() );
+                expectation.insert( indx, step.to_string() );
+            }
+        }
+        assert!( store.iter().eq( expectation.iter() ));
+    }// }}}
+    // }}}
+}// }}}
@@ -20,21 +20,27 @@
 //! command is parsed, and so is a safe assumption.
 // *** Bring in to namespace *** {{{
 use std::collections::hash_map::HashMap;
-use std::process::exit;
-use std::io::{Write, BufRead, BufWriter, stdout, StdoutLock, stdin};
 use std::ffi::OsStr;
 
 use buf::*;
 use error::*;
 use parse::*;
-use io::get_input;
-use ::{EditorState, EditorMode, print_help, print_msg, term_size, Change};
+use io::{Terminal, get_input, command_filter, program_filter};
+use lines::LineStore;
+use encoding::{escaped_byte, LineEnding};
+use journal::{self, file_age, format_age};
+use ::{EditorState, EditorMode, print_help, print_msg, print_line};
 use self::NotableLine::*;
 // ^^^ Bring in to namespace ^^^ }}}
 
 // *** Attributes *** {{{
-const NUM_OPERATIONS: usize = 27;
+const NUM_OPERATIONS: usize = 32;
 const COMMAND_PREFIX: &'static str = "@";
+const SHELL_PREFIX: &'static str = "!";
+/// replacement standing for the replacement of the last substitution
+const SUB_LAST_REPLACEMENT: &'static str = "%";
+/// lines printed by `z` when terminal size is unknown
+const DEFAULT_SCROLL_LINES: usize = 22;
 // ^^^ Attributes ^^^ }}}
 
 // *** Constants *** {{{
@@ -62,6 +68,20 @@ impl Operations {// {{{
         let mut _operation_map: HashMap<char, OpData> =
             HashMap::with_capacity( NUM_OPERATIONS );
         // Insert operations and data   //{{{
+        _operation_map.insert( '!',// {{{
+                                OpData{ function: Box::new(shell),
+                                        default_initial_address:
+                                            LineNotApplicable,
+                                        default_final_address:
+                                            LineNotApplicable,
+                                }
+        );// }}}
+        _operation_map.insert( '=',// {{{
+                                OpData{ function: Box::new(line_number),
+                                        default_initial_address: LastLine,
+                                        default_final_address: LastLine,
+                                }
+        );// }}}
         _operation_map.insert( 'a',// {{{
                                 OpData{ function: Box::new(append),
                                         default_initial_address: CurrentLine,
@@ -96,6 +116,14 @@ impl Operations {// {{{
                                             LineNotApplicable,
                                 }
         );// }}}
+        _operation_map.insert( 'F',// {{{
+                                OpData{ function: Box::new(text_format),
+                                        default_initial_address:
+                                            LineNotApplicable,
+                                        default_final_address:
+                                            LineNotApplicable,
+                                }
+        );// }}}
         _operation_map.insert( 'f',// {{{
                                 OpData{ function: Box::new(filename),
                                         default_initial_address:
@@ -217,6 +245,14 @@ impl Operations {// {{{
                                             LineNotApplicable,
                                 }
         );// }}}
+        _operation_map.insert( 'U',// {{{
+                                OpData{ function: Box::new(redo),
+                                        default_initial_address:
+                                            LineNotApplicable,
+                                        default_final_address:
+                                            LineNotApplicable,
+                                }
+        );// }}}
         _operation_map.insert( 'v',// {{{
                                 OpData{ function: Box::new(global_inverse),
                                         default_initial_address: FirstLine,
@@ -242,6 +278,14 @@ impl Operations {// {{{
                                         default_final_address: LastLine,
                                 }
         );// }}}
+        _operation_map.insert( 'z',// {{{
+                                OpData{ function: Box::new(scroll),
+                                        default_initial_address:
+                                            CurrentPlusOneLine,
+                                        default_final_address:
+                                            CurrentPlusOneLine,
+                                }
+        );// }}}
         //}}}
         Operations { operation_map: _operation_map }
     }// }}}
@@ -253,7 +297,23 @@ impl Operations {// {{{
             true => {
                 let ref op_to_execute = self.operation_map
                     .get( &command.operation ).unwrap().function;
-                op_to_execute( state, command )
+                // z prints its own lines in the form the suffix asks for
+                let print_suffix = match command.operation {
+                    'z' => None,
+                    _ => command.print_suffix,
+                };
+                try!( op_to_execute( state, command ));
+                match print_suffix {
+                    Some( suffix ) => {
+                        let current = state.buffer.get_current_address();
+                        if current == 0 {
+                            return Err( RedError::GetLineOutOfBounds{
+                                    address: current } );
+                        }
+                        print_with( state, self, suffix, current, current )
+                    },
+                    None => Ok( () ),
+                }
             },
             false => {
                 Err(RedError::InvalidOperation{ operation: command.operation })
@@ -272,6 +332,7 @@ impl Operations {// {{{
                                 address_final: address,
                                 operation: 'p',
                                 parameters: "",
+                                print_suffix: None,
                                 operations: &self,
                             };
             } else {
@@ -289,34 +350,66 @@ impl Operations {// {{{
 // ^^^ Data Structures ^^^ }}}
 
 // *** Functions *** {{{
-/// Avoid `unused` warnings for functions that don't modify mode// {{{
-fn mode_noop( mode: &mut EditorMode ) -> EditorMode {// {{{
-    match mode {
-        &mut EditorMode::Command => EditorMode::Command,
-        &mut EditorMode::Insert => EditorMode::Insert,
+/// Run shell command, or filter address range through it// {{{
+///
+/// Without an address, `!command` runs command and displays its output;
+/// with an address range, the lines are fed to command and replaced by
+/// its output, which can be undone as a single step
+fn shell( state: &mut EditorState, command: Command )
+        -> Result<(), RedError> {// {{{
+    assert_eq!( '!', command.operation );
+    try!( state.buffer.check_input( &BufferInput::Command(
+            command.parameters.to_string() )));
+    let ( shell_command, expanded ) = try!(
+        parse_shell_command( command.parameters, state ));
+    if expanded {
+        print_msg( state, &shell_command );
     }
-}// }}}
+    state.last_shell_command = shell_command.clone();
+    if command.address_initial == 0 && command.address_final == 0 {
+        let output = try!( command_filter( &shell_command, "" ));
+        try!( state.terminal.write( &output ));
+        print_msg( state, SHELL_PREFIX );
+        return Ok( () );
+    }
+    state.u_reset();
+    state.u_lock();
+    let ( _initial, _final ) = default_addrs( state, &command );
+    let mut input: String = String::new();
+    for line in state.buffer.range_iterator( _initial, _final ) {
+        input.push_str( line );
+        input.push( '\n' );
+    }
+    let output = try!( command_filter( &shell_command, &input ));
+    state.u_deleting_lines( _initial, _final );
+    for _ in _initial .. ( _final + 1 ) {
+        try!( state.buffer.delete_line( _initial ) );
+    }
+    let mut address = _initial - 1;
+    for line in output.lines() {
+        state.buffer.append_line( address, line );
+        address += 1;
+    }
+    if address >= _initial {
+        state.u_added_lines( _initial, address );
+    }
+    state.buffer.set_current_address( address );
+    print_msg( state, output.len() );
+    Ok( () )
+}//}}}
 // }}}
-/// A simple placeholder function for unimplemented features// {{{
-fn placeholder( state: &mut EditorState, command: Command)//{{{
-        -> Result<(), RedError> {
-    print_msg( state, &format!(
-            "Operation not yet implemented: {}", command.operation ));
-    state.mode = mode_noop( &mut state.mode );
-    match state.buffer.get_file_name() {
-        Some( file_name ) => {
-            print_msg( state, &format!(
-                    "Continuing work on {}", file_name.to_str()
-                            .unwrap_or("<invalid UTF-8>")) );
-            return Err(
-                RedError::InvalidOperation{ operation: command.operation } );
-        }
-        None => {
-            return Err(
-                RedError::InvalidOperation{ operation: command.operation } );
-        }
+/// Print line number of addressed line; current line is unchanged// {{{
+fn line_number( state: &mut EditorState, command: Command )
+        -> Result<(), RedError> {// {{{
+    assert_eq!( '=', command.operation );
+    if !command.parameters.is_empty() {
+        return Err( RedError::ParameterSyntax{
+                parameter: command.parameters.to_string() });
     }
-}// }}}
+    let ( _, _final ) = default_addrs( state, &command );
+    try!( state.terminal.write( &format!( "{}\n", _final )));
+    Ok( () )
+}//}}}
 // }}}
 fn append( state: &mut EditorState, command: Command )
         -> Result<(), RedError> {// {{{
@@ -337,10 +430,10 @@ fn change( state: &mut EditorState, command: Command )
     let ( _initial, _final ) = default_addrs( state, &command );
     let delete_command = Command{ address_initial: _initial,
             address_final: _final, operation: 'd', parameters: "",
-            operations: command.operations };
+            print_suffix: None, operations: command.operations };
     let insert_command = Command{ address_initial: _initial,
             address_final: _initial, operation: 'i', parameters: "",
-            operations: command.operations };
+            print_suffix: None, operations: command.operations };
     try!( delete( state, delete_command ) );
     try!( insert( state, insert_command ) );
     Ok( () )
@@ -357,62 +450,230 @@ fn delete( state: &mut EditorState, command: Command )
         // NOTE: lines move as you delete them - don't increment!
         try!( state.buffer.delete_line( _initial ) );
     }
-    state.buffer.set_current_address( _initial - 1 );
+    // current line is the one after those deleted, or else the last one
+    let num_lines = state.buffer.num_lines();
+    state.buffer.set_current_address( if _initial <= num_lines { _initial }
+                                      else { num_lines } );
     Ok( () )
 }//}}}
 fn edit( state: &mut EditorState, command: Command )
         -> Result<(), RedError> {// {{{
     assert_eq!( 'e', command.operation );
-    let _ = try!( state.buffer.on_close() );
+    try!( state.buffer.check_input( &edit_input( command.parameters )));
+    if state.buffer.is_modified() && state.buffer.changed_on_disk( "" ) {
+        // asking is warning enough; changes go as with `E`
+        try!( confirm_disk_change( state, "discard changes" ));
+    } else {
+        let _ = try!( state.buffer.on_close() );
+    }
     edit_unsafe( state, Command{ address_initial: command.address_initial,
         address_final: command.address_final, operation: 'E',
-        parameters: command.parameters, operations: command.operations, })
+        parameters: command.parameters, print_suffix: None,
+        operations: command.operations, })
 }//}}}
 fn edit_unsafe( state: &mut EditorState, command: Command )
         -> Result<(), RedError> {// {{{
     assert_eq!( 'E', command.operation );
-
     let content = command.parameters;
-    if &content[0..1] == COMMAND_PREFIX {  // process command
-        match Buffer::new( BufferInput::Command(content[1..].to_string() )) {
+    let input = edit_input( content );
+    // refuse before anything belonging to the current buffer is dropped
+    try!( state.buffer.check_input( &input ));
+    let restricted = state.buffer.is_restricted();
+
+    // unsaved changes are dropped along with the buffer; so is their journal,
+    // before it can be mistaken for a stale one offered for recovery
+    let _ = state.buffer.discard_journal();
+    if let Err( e ) = replace_buffer( state, content, input ) {
+        // current buffer is kept, so it needs its journal back
+        let _ = state.buffer.store_buffer();
+        return Err( e );
+    }
+    if restricted {
+        state.buffer.restrict();
+    }
+    Ok( () )
+}//}}}
+/// Open new buffer on file or command output in place of current one// {{{
+fn replace_buffer( state: &mut EditorState, content: &str,// {{{
+                   input: BufferInput ) -> Result<(), RedError> {
+    if let BufferInput::Command(_) = input {  // process command
+        match Buffer::new( input ) {
             Ok( _buffer ) => {
-                EditorState::new( _buffer );
+                state.set_buffer( _buffer );
             },
             Err(e) => {
                 return Err(e);
             },
         };
-        print_msg( &state, &format!( "Now editing output of command: {}",
-                                     state.buffer.get_file_name()
-                                     .unwrap_or( OsStr::new("<untitled>") )
-                                     .to_str()
-                                     .unwrap_or( "<invalid UTF-8>" ) ));
+        let message = format!( "Now editing output of command: {}",
+                               state.buffer.get_file_name()
+                               .unwrap_or( OsStr::new("<untitled>") )
+                               .to_str()
+                               .unwrap_or( "<invalid UTF-8>" ) );
+        print_msg( state, message );
     } else {                    // process file
-        match Buffer::new(BufferInput::File( content.to_string() )) {
+        // opening the buffer replaces any stale temp file, so ask first
+        let recovered = match recovery_file( content ) {
+            Some( temp_file ) => try!( offer_recovery( state, content,
+                                                       &temp_file )),
+            None => None,
+        };
+        match Buffer::new( input ) {
             Ok( _buffer ) => {
-                state.buffer = _buffer;
+                state.set_buffer( _buffer );
             },
             Err(e) => {
                 return Err(e);
             },
         };
-        print_msg( &state, &format!( "Now editing file: {}",
-                                     state.buffer.get_file_name()
-                                     .unwrap_or( OsStr::new("<untitled>") )
-                                     .to_str()
-                                     .unwrap_or( "<invalid UTF-8>" ) ));
+        match recovered {
+            Some( lines ) => {
+                try!( state.buffer.restore_lines( lines ));
+                print_msg( state, "recovered unsaved changes" );
+            },
+            None => {},
+        }
+        let message = format!( "Now editing file: {}",
+                               state.buffer.get_file_name()
+                               .unwrap_or( OsStr::new("<untitled>") )
+                               .to_str()
+                               .unwrap_or( "<invalid UTF-8>" ) );
+        print_msg( state, message );
+        if !state.buffer.get_format().final_newline {
+            // it stays missing when written; `F eol` adds it
+            print_msg( state, "no newline at end of file" );
+        }
+    }
+    Ok( () )
+}// }}}
+// }}}
+/// Ask whether to go ahead although current file changed on disk// {{{
+///
+/// Anything but `y` is no; in batch mode the answer is always no
+fn confirm_disk_change( state: &mut EditorState, action: &str )// {{{
+        -> Result<(), RedError> {
+    if state.batch {
+        return Err( RedError::FileChanged );
+    }
+    let file_name = state.buffer.get_file_name()
+        .map( |f| f.to_string_lossy().into_owned() )
+        .unwrap_or( "file".to_string() );
+    let answer = try!( state.terminal.confirm( &format!(
+            "{} changed on disk since read or written; {} anyway? (y/n) [n]\n",
+            file_name, action )));
+    match answer.trim() {
+        "y" => Ok( () ),
+        _ => Err( RedError::FileChanged ),
+    }
+}// }}}
+// }}}
+/// Return what `e` parameter names: command output or file// {{{
+fn edit_input( content: &str ) -> BufferInput {// {{{
+    if content.starts_with( COMMAND_PREFIX ) {
+        BufferInput::Command( content[COMMAND_PREFIX.len()..].to_string() )
+    } else {
+        BufferInput::File( content.to_string() )
+    }
+}// }}}
+// }}}
+/// Ask user what to do with temp file left by an earlier session// {{{
+///
+/// Returns the recovered lines if the user chooses to recover them; an
+/// empty answer (or end of input) recovers, so nothing is lost by default
+fn offer_recovery( state: &mut EditorState, file_name: &str,// {{{
+                   temp_file: &OsStr ) -> Result<Option<LineStore>, RedError> {
+    let lines = match journal::replay( temp_file ) {
+        Ok( x ) => x,
+        Err( e ) => {
+            print_help( state, &format!(
+                    "unable to read recovery file {}: {:?}",
+                    temp_file.to_string_lossy(), e ));
+            return Ok( None );
+        },
+    };
+    let temp_age = file_age( temp_file );
+    let write_age = file_age( file_name );
+    print_line( state, format!( "found unsaved changes to {} in {} ({} lines)",
+              file_name, temp_file.to_string_lossy(), lines.len() ));
+    match ( temp_age, write_age ) {
+        ( Some( t ), Some( f ) ) => print_line( state, format!(
+                "recovery file is {} old; {} was last written {} ago{}",
+                format_age( t ), file_name, format_age( f ),
+                if f < t { " (file is newer!)" } else { "" } )),
+        ( Some( t ), None ) => print_line( state, format!(
+                "recovery file is {} old; {} {}",
+                format_age( t ), file_name, "does not exist" )),
+        _ => {},
     }
+    if state.batch {
+        // a script can't answer; keep the changes for an interactive session
+        print_line( state,
+                    "open the file interactively to recover or discard them" );
+        return Err( RedError::RecoveryPending );
+    }
+    loop {
+        let answer = try!( state.terminal.confirm( &format!(
+                "(r)ecover, (d)iff with {}, or (x) discard? [r]\n",
+                file_name )));
+        match answer.trim() {
+            "" | "r" => return Ok( Some( lines )),
+            "x" => return Ok( None ),
+            "d" => {
+                let mut content = String::new();
+                for line in lines.iter() {
+                    content.push_str( line );
+                    content.push( '\n' );
+                }
+                let diff = try!( program_filter( "diff",
+                        &[ "-u", file_name, "-" ], &content ));
+                try!( state.terminal.write( &diff ));
+            },
+            _ => print_help( state, "answer r, d, or x" ),
+        }
+    }
+}// }}}
+// }}}
+/// Show or change line endings used when writing// {{{
+///
+/// `F` shows them; `F lf` or `F crlf` sets the line ending, and `F eol`
+/// or `F noeol` whether the last line has one. Not undoable
+fn text_format( state: &mut EditorState, command: Command )
+        -> Result<(), RedError> {// {{{
+    assert_eq!( 'F', command.operation );
+    let mut format = state.buffer.get_format();
+    if command.parameters.is_empty() {
+        let message = format!( "line endings: {}{}\n",
+                               format.line_ending.name(),
+                               match format.final_newline {
+                                   true => "",
+                                   false => ", no newline at end of file" } );
+        try!( state.terminal.write( &message ));
+        return Ok( () );
+    }
+    for parameter in command.parameters.split_whitespace() {
+        match parameter {
+            "lf" => format.line_ending = LineEnding::Lf,
+            "crlf" => format.line_ending = LineEnding::CrLf,
+            "eol" => format.final_newline = true,
+            "noeol" => format.final_newline = false,
+            _ => return Err( RedError::ParameterSyntax{ parameter:
+                "F: ".to_string() + parameter }),
+        }
+    }
+    state.buffer.set_format( format );
     Ok( () )
 }//}}}
+// }}}
 fn filename( state: &mut EditorState, command: Command )
         -> Result<(), RedError> {// {{{
     assert_eq!( 'f', command.operation );
     if command.parameters == "" {
-        match state.buffer.get_file_name() {
-            Some(f) => println!( "filename: {}", f.to_str()
+        let message = match state.buffer.get_file_name() {
+            Some(f) => format!( "filename: {}", f.to_str()
                             .unwrap_or("<invalid UTF-8>") ),
-            None => println!( "no filename currently set" ),
-        }
+            None => "no filename currently set".to_string(),
+        };
+        try!( state.terminal.write( &( message + "\n" )));
     } else {
         try!( state.buffer.set_file( command.parameters ));
     }
@@ -426,10 +687,14 @@ fn filename( state: &mut EditorState, command: Command )
 fn global( state: &mut EditorState, command: Command )
         -> Result<(), RedError> {// {{{
     assert_eq!( 'g', command.operation );
+    // lock undo for the duration so the whole command is one step
+    state.u_reset();
+    state.u_lock();
     let ( _initial, _final ) = default_addrs( state, &command );
     let ( pattern, commands ) = try!(parse_global_op(command.parameters));
+    let re = try!( compile_regex( &try!( state.use_regex( pattern ))));
     for address in _initial .. _final + 1 {
-        if state.buffer.does_match( pattern, address ) {
+        if state.buffer.does_match( &re, address ) {
             try!( command.operations.execute_list( state, commands, address ));
         }
     }
@@ -438,10 +703,13 @@ fn global( state: &mut EditorState, command: Command )
 fn global_interactive( state: &mut EditorState,//{{{
                        command: Command ) -> Result<(), RedError> {
     assert_eq!( 'G', command.operation );
+    state.u_reset();
+    state.u_lock();
     let ( _initial, _final ) = default_addrs( state, &command );
     let mut input: String = String::new();
     let mut last_input: String = String::new();
     let ( pattern, commands ) = try!(parse_global_op(command.parameters));
+    let re = try!( compile_regex( &try!( state.use_regex( pattern ))));
     // make sure no additional text after /re/
     if !commands.is_empty() {
         return Err( RedError::ParameterSyntax{
@@ -451,14 +719,15 @@ fn global_interactive( state: &mut EditorState,//{{{
     let prompt_save = state.prompt.clone();
     state.prompt = "(G)%".to_string();
     for address in _initial .. _final + 1 {
-        if state.buffer.does_match( pattern, address ) {
+        if state.buffer.does_match( &re, address ) {
             state.buffer.set_current_address( address );
             print_numbered( state, Command{
                                     address_initial: address,
                                     address_final: address,
                                     operation: 'n',
                                     operations: command.operations,
-                                    parameters: "" }).expect(
+                                    parameters: "",
+                                    print_suffix: None }).expect(
                     "global_interactive: line matching regex doesn't exist!" );
             input = try!( get_input( input, state ));
             if input.trim() == "&" {
@@ -475,15 +744,20 @@ fn global_interactive( state: &mut EditorState,//{{{
 fn help_recall( state: &mut EditorState, command: Command )
         -> Result<(), RedError> {// {{{
     assert_eq!( 'h', command.operation );
-    placeholder( state, command )
+    // explain last error even when help output is off
+    if !state.last_help.is_empty() {
+        try!( state.terminal.write( &format!( "{}\n", state.last_help )));
+    }
+    Ok( () )
 }//}}}
 fn help_tgl( state: &mut EditorState, command: Command )
         -> Result<(), RedError> {// {{{
     assert_eq!( 'H', command.operation );
     state.show_help = !state.show_help;
-    println!("help output set to {:?}", match state.show_help {
+    let message = format!( "help output set to {:?}\n", match state.show_help {
         true => "on",
         false => "off", });
+    try!( state.terminal.write( &message ));
     Ok( () )
 }//}}}
 fn insert( state: &mut EditorState, command: Command )
@@ -545,14 +819,12 @@ fn lines_list( state: &mut EditorState, command: Command )
         -> Result<(), RedError> {// {{{
     assert_eq!( 'l', command.operation );
     let ( _initial, _final ) = default_addrs( state, &command );
-    let stdout = stdout();
-    let handle = stdout.lock();
-    let mut writer = BufWriter::new( handle );
+    let mut output = String::new();
     let mut ch_written: usize = 0;
     let line_prefix: &str;  // ! to indicate unknown screen size
     let term_width: usize;
     let term_height: usize;
-    if let Some((w, h)) = term_size::dimensions() {
+    if let Some((w, h)) = state.terminal.size() {
         line_prefix = "";
         term_width = w;
         term_height = h;
@@ -563,39 +835,86 @@ fn lines_list( state: &mut EditorState, command: Command )
     }
     for address in _initial .. _final + 1 {
         let line = state.buffer.get_line_content( address ).unwrap_or("");
-        try!( writer.write( line_prefix.as_bytes() )
-              .map_err(|_| RedError::Stdout));
+        output.push_str( line_prefix );
         ch_written += line_prefix.len();
         for ch in line.chars() {
-            for _ch in ch.escape_default() {
-                try!( writer.write( &[_ch as u8] )
-                          .map_err(|_| RedError::Stdout));
+            // bytes that aren't UTF-8 are shown in octal, as ed does
+            let escaped: String = match escaped_byte( ch ) {
+                Some( byte ) => format!( "\\{:03o}", byte ),
+                None => ch.escape_default().collect(),
+            };
+            for _ch in escaped.chars() {
+                output.push( _ch );
                 ch_written += 1;
                 if line_prefix.len() == 0 && ch_written ==
                     ( term_width - line_prefix.len() ) * ( term_height - 1 ) {
-                    try!( writer.flush().map_err(|_| RedError::Stdout));
-                    prompt_for_more( &mut writer );
+                    try!( state.terminal.write( &output ));
+                    output.clear();
+                    try!( prompt_for_more( &mut *state.terminal ));
                 }
             }
         }
-        try!( writer.write( "$\n".as_bytes() ).map_err(|_| RedError::Stdout));
+        output.push_str( "$\n" );
         ch_written += 1;
-        ch_written += term_width - ( ch_written % term_width );
-        try!( writer.flush().map_err(|_| RedError::Stdout));
+        if term_width > 0 {
+            ch_written += term_width - ( ch_written % term_width );
+        }
+        try!( state.terminal.write( &output ));
+        output.clear();
+    }
+    Ok( () )
+}//}}}
+/// Print a screenful of lines starting at address// {{{
+///
+/// `z[n][p|l|n]`: n is the number of lines to print, by default one less
+/// than the terminal height; the print suffix selects how lines are
+/// printed rather than printing the current line afterward.
+/// The last line printed becomes the current line
+fn scroll( state: &mut EditorState, command: Command )
+        -> Result<(), RedError> {// {{{
+    assert_eq!( 'z', command.operation );
+    let ( _, _initial ) = default_addrs( state, &command );
+    if _initial == 0 || _initial > state.buffer.num_lines() {
+        return Err( RedError::GetLineOutOfBounds{ address: _initial } );
     }
+    let count = command.parameters;
+    let window: usize = if count.is_empty() {
+        match state.terminal.size() {
+            Some(( _, h )) if h > 1 => h - 1,
+            _ => DEFAULT_SCROLL_LINES,
+        }
+    } else {
+        try!( count.parse().map_err( |_| RedError::ParameterSyntax{
+                parameter: command.parameters.to_string() }))
+    };
+    let suffix = command.print_suffix.unwrap_or( 'p' );
+    let mut _final = _initial + window.saturating_sub( 1 );
+    if _final > state.buffer.num_lines() {
+        _final = state.buffer.num_lines();
+    }
+    try!( print_with( state, command.operations, suffix, _initial, _final ));
+    state.buffer.set_current_address( _final );
     Ok( () )
 }//}}}
+// }}}
+/// Print address range using print operation named by suffix// {{{
+fn print_with( state: &mut EditorState, operations: &Operations,// {{{
+               suffix: char, address_initial: usize, address_final: usize )
+        -> Result<(), RedError> {
+    let print_command = Command{ address_initial: address_initial,
+            address_final: address_final, operation: suffix,
+            parameters: "", print_suffix: None, operations: operations };
+    match suffix {
+        'l' => lines_list( state, print_command ),
+        'n' => print_numbered( state, print_command ),
+        _ => print( state, print_command ),
+    }
+}// }}}
+// }}}
 /// Prompts the user to press enter and waits until they do// {{{
-fn prompt_for_more( stdout_writer: &mut BufWriter<StdoutLock> ) {// {{{
-    stdout_writer.write( "--<press enter to continue>--".as_bytes() )
-        .expect( "prompt_for_more: error writing to stdout" );
-    stdout_writer.flush().expect( "prompt_for_more: error writing to stdout" );
-    let stdin = stdin();
-    let mut handle = stdin.lock();
-    let mut buf = String::new();
-    handle.read_line( &mut buf ).expect( "error reading keystroke" );
-    stdout_writer.write( &['\n' as u8] )
-        .expect( "prompt_for_more: error writing to stdout" );
+fn prompt_for_more( terminal: &mut dyn Terminal ) -> Result<(), RedError> {// {{{
+    try!( terminal.confirm( "--<press enter to continue>--" ));
+    terminal.write( "\n" )
 }// }}}
 // }}}
 fn move_lines( state: &mut EditorState, command: Command )
@@ -642,8 +961,8 @@ fn print_numbered( state: &mut EditorState,//{{{
         .skip( _initial - 1 )
         .take(( _final + 1 ) - _initial )
         .map( |(x, y)| ( x+1, y )) {
-        print!( "{:width$}|", _num, width = _width );
-        println!("{}", _line );
+        try!( state.terminal.write( &format!( "{:width$}|{}\n",
+                                              _num, _line, width = _width )));
     }
     state.buffer.set_current_address( _final );
     Ok( () )
@@ -656,17 +975,14 @@ fn print_numbered( state: &mut EditorState,//{{{
 /// be the address of the last line of the state.buffer (in other
 /// words, the lines number of the last line)
 ///
-/// # Panics
-/// if println! panics, which happens if it fails to write
-/// to io::stdout()
-///
 fn print( state: &mut EditorState, command: Command )//{{{
             -> Result<(), RedError> {
     assert_eq!( 'p', command.operation );
     let ( _initial, _final ) = default_addrs( state, &command );
     for indx in _initial .. ( _final + 1 ) {
-        println!("{}", state.buffer.get_line_content( indx ).expect(
-                "ops::print: called get_line_content on out-of-range line" ) );
+        let line = state.buffer.get_line_content( indx ).expect(
+                "ops::print: called get_line_content on out-of-range line" );
+        try!( state.terminal.write( &format!( "{}\n", line )));
     }
     state.buffer.set_current_address( _final );
     // TODO: Drop this? Or Keep to avoid unused warnings?
@@ -674,45 +990,117 @@ fn print( state: &mut EditorState, command: Command )//{{{
     Ok( () )
 }// }}}
 // }}}
-/// Toggles (sets?) commant prompt
+/// Toggles display of command prompt
+///
+/// The prompt shown is DEFAULT_PROMPT, or the one given by the `-p` flag
 fn prompt( state: &mut EditorState, command: Command )//{{{
             -> Result<(), RedError> {
-    assert_eq!( 'p', command.operation );
-    placeholder( state, command )
+    assert_eq!( 'P', command.operation );
+    state.show_prompt = !state.show_prompt;
+    Ok( () )
 }// }}}
 // }}}
 /// Exit program// {{{
 ///
-/// Make sure all state.buffers have been saved
+/// Make sure all state.buffers have been saved; with unsaved changes, the
+/// first `q` only warns, and a second one in a row quits anyway
 ///
-/// Delete all temprary storage
+/// Delete all temprary storage; the caller ends the session on
+/// RedError::Quit
 fn quit( state: &mut EditorState, command: Command )//{{{
             -> Result<(), RedError> {
     assert_eq!( 'q', command.operation );
+    if state.buffer.is_modified() && !state.quit_warned {
+        if state.buffer.changed_on_disk( "" ) {
+            // asking is warning enough
+            try!( confirm_disk_change( state, "quit" ));
+        } else {
+            state.quit_warned = true;
+            return Err( RedError::NoDestruct );
+        }
+    }
     match state.buffer.on_close() {
-        Ok( _ ) => exit( error_code( RedError::Quit ) as i32),
-        Err( _ ) => exit( error_code( RedError::NoDestruct ) as i32),
+        Ok( _ ) => {},
+        Err( _ ) => {
+            // leave unsaved changes on disk for recovery
+            let _ = state.buffer.flush_journal();
+        },
     }
+    Err( RedError::Quit )
 }// }}}
 //}}}
+/// Append file content or command output after address// {{{
+///
+/// `r file` reads file, `r !command` reads output of command; with no
+/// parameter, the current file is read
 fn read( state: &mut EditorState, command: Command )
         -> Result<(), RedError> {// {{{
     assert_eq!( 'r', command.operation );
+    state.u_reset();
+    state.u_lock();
     let ( _initial, _final ) = default_addrs( state, &command );
-    placeholder( state, command )
+    let content: BufferInput;
+    if command.parameters.starts_with( SHELL_PREFIX ) {
+        content = BufferInput::Command(
+            command.parameters[SHELL_PREFIX.len()..].to_string() );
+    } else if command.parameters.is_empty() {
+        match state.buffer.get_file_path() {
+            Some( file_path ) => content = BufferInput::File(
+                    file_path.to_str().unwrap_or("").to_string() ),
+            None => return Err( RedError::ParameterSyntax{
+                parameter: "read: no current file name".to_string() }),
+        }
+    } else {
+        content = BufferInput::File( command.parameters.to_string() );
+    }
+    if !command.parameters.is_empty() {
+        try!( state.buffer.check_input( &content ));
+    }
+    let ( num_lines, num_bytes, newline_appended ) = try!(
+        state.buffer.read_lines( _final, &content ));
+    if num_lines > 0 {
+        state.u_added_lines( _final + 1, _final + num_lines );
+    }
+    print_msg( state, num_bytes );
+    if newline_appended {
+        print_msg( state, "newline appended" );
+    }
+    Ok( () )
 }//}}}
+// }}}
 fn substitute( state: &mut EditorState, command: Command )
         -> Result<(), RedError> {// {{{
     assert_eq!( 's', command.operation );
     state.u_reset();
     state.u_lock();
     let ( _initial, _final ) = default_addrs( state, &command );
+    let mut sub_parms: Substitution;
+    if is_substitution_repeat( command.parameters ) {
+        // bare `s` repeats last substitution, optionally with new flags
+        sub_parms = Substitution{ to_match: String::new(),
+                to_sub: SUB_LAST_REPLACEMENT.to_string(),
+                which: match command.parameters {
+                    "" => state.last_sub_which,
+                    x => try!( parse_substitution_flags( x )),
+                },
+        };
+    } else {
+        sub_parms = try!( parse_substitution_parameter( command.parameters ));
+    }
+    sub_parms.to_match = try!( state.use_regex( &sub_parms.to_match ));
+    if sub_parms.to_sub == SUB_LAST_REPLACEMENT {
+        sub_parms.to_sub = match state.last_replacement {
+            Some( ref x ) => x.clone(),
+            None => return Err( RedError::NoPreviousSubstitution ),
+        };
+    }
+    state.last_replacement = Some( sub_parms.to_sub.clone() );
+    state.last_sub_which = sub_parms.which;
     state.u_deleting_lines( _initial, _final );
-    let sub_parms: Substitution = try!( parse_substitution_parameter(
-            command.parameters ));
-    state.buffer.substitute( &sub_parms.to_match, &sub_parms.to_sub,
-                             sub_parms.which, _initial, _final );
-    state.u_added_lines( _initial, _final );
+    let added = try!( state.buffer.substitute( &sub_parms.to_match,
+                      &sub_parms.to_sub, sub_parms.which, _initial, _final ));
+    state.u_added_lines( _initial, _final + added );
+    state.buffer.set_current_address( _final + added );
     Ok( () )
 }//}}}
 fn transfer( state: &mut EditorState, command: Command )
@@ -733,42 +1121,36 @@ fn transfer( state: &mut EditorState, command: Command )
                                  destination + 1 + ( _final - _initial ));
     Ok( () )
 }//}}}
+/// Undo most recent change; repeat to step further back in history// {{{
 fn undo( state: &mut EditorState, command: Command )
         -> Result<(), RedError> {// {{{
     assert_eq!( 'u', command.operation );
-    let address = state.u_get_wascurrent_address();
-    let markers = state.u_get_markers();
-    let mut changes = state.u_get_changes();
-    state.u_unlock();
-    state.u_reset();
-    state.u_lock();
-    loop {
-        match changes.pop() {
-            Some( Change::Add{ address: _address }) => {
-                state.u_deleting_line( _address );
-                try!( state.buffer.delete_line( _address ) );
-            },
-            Some( Change::Remove{ address: _address, content: _content }) => {
-                state.u_added_line( _address );
-                state.buffer.append_line( _address - 1, &_content );
-            },
-            None => break,
-        }
+    match try!( state.u_undo() ) {
+        true => Ok( () ),
+        false => Err( RedError::NothingToUndo ),
     }
-    state.buffer.set_current_address( address );
-    for indx in ( 'a' as u8 ) .. ( 'z' as u8 ) + 1 {
-        state.buffer.set_marker( indx as char,
-            markers[ ( indx as usize ) - ( 'a' as usize ) ] );
+}//}}}
+// }}}
+/// Redo most recently undone change// {{{
+fn redo( state: &mut EditorState, command: Command )
+        -> Result<(), RedError> {// {{{
+    assert_eq!( 'U', command.operation );
+    match try!( state.u_redo() ) {
+        true => Ok( () ),
+        false => Err( RedError::NothingToRedo ),
     }
-    Ok( () )
 }//}}}
+// }}}
 fn global_inverse( state: &mut EditorState,//{{{
                    command: Command ) -> Result<(), RedError> {
     assert_eq!( 'v', command.operation );
+    state.u_reset();
+    state.u_lock();
     let ( _initial, _final ) = default_addrs( state, &command );
     let ( pattern, commands ) = try!(parse_global_op(command.parameters));
+    let re = try!( compile_regex( &try!( state.use_regex( pattern ))));
     for address in _initial .. _final + 1 {
-        if !state.buffer.does_match( pattern, address ) {
+        if !state.buffer.does_match( &re, address ) {
             try!( command.operations.execute_list( state, commands, address ));
         }
     }
@@ -777,10 +1159,13 @@ fn global_inverse( state: &mut EditorState,//{{{
 fn global_inverse_interactive( state: &mut EditorState,//{{{
                                command: Command ) -> Result<(), RedError> {
     assert_eq!( 'V', command.operation );
+    state.u_reset();
+    state.u_lock();
     let ( _initial, _final ) = default_addrs( state, &command );
     let mut input: String = String::new();
     let mut last_input: String = String::new();
     let ( pattern, commands ) = try!(parse_global_op(command.parameters));
+    let re = try!( compile_regex( &try!( state.use_regex( pattern ))));
     // make sure no additional text after /re/
     if !commands.is_empty() {
         return Err( RedError::ParameterSyntax{
@@ -790,14 +1175,15 @@ fn global_inverse_interactive( state: &mut EditorState,//{{{
     let prompt_save = state.prompt.clone();
     state.prompt = "(G)%".to_string();
     for address in _initial .. _final + 1 {
-        if !state.buffer.does_match( pattern, address ) {
+        if !state.buffer.does_match( &re, address ) {
             state.buffer.set_current_address( address );
             print_numbered( state, Command{
                                     address_initial: address,
                                     address_final: address,
                                     operation: 'n',
                                     operations: command.operations,
-                                    parameters: "" }).expect(
+                                    parameters: "",
+                                    print_suffix: None }).expect(
                     "global_interactive: line matching regex doesn't exist!" );
             input = try!( get_input( input, state ));
             if input.trim() == "&" {
@@ -818,6 +1204,9 @@ fn write_to_disk( state: &mut EditorState,//{{{
     // TODO: Drop this? Or Keep to avoid unused warnings?
     state.mode = EditorMode::Command;
     let ( _initial, _final ) = default_addrs( state, &command );
+    if state.buffer.changed_on_disk( command.parameters ) {
+        try!( confirm_disk_change( state, "overwrite it" ));
+    }
     state.buffer.write_to_disk( command.parameters, false, _initial, _final )
 }// }}}
 // }}}
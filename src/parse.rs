@@ -10,7 +10,8 @@
  */
 
 // Bring in to namespace {{{
-use std::str::Bytes;
+use std::str::{Bytes, Chars};
+use std::ffi::OsStr;
 
 use ::regex::{Regex, Captures};
 
@@ -18,7 +19,10 @@ use error::*;
 use io::*;
 use buf::*;
 use ops::Operations;
-use ::{EditorState, EditorMode};
+use posix::RegexSyntax;
+use journal::{FlushPolicy, TEMP_FILE_PREFIX, list_sessions, replay,
+              file_age, format_age};
+use ::{EditorState, EditorMode, print_msg, print_help};
 
 // ^^^ Bring in to namespace ^^^ }}}
 
@@ -32,8 +36,8 @@ const ADDR_REGEX_RITHMETIC: &'static str =
 const ADDR_REGEX_ADDORSUBT: &'static str =
         r#"\s*((\+|-)\s*(\d*))\s*(((\+|-)\s*(\d*))*)\s*"#;
 const ADDR_REGEX_MARKER:    &'static str = r#"'([:lower:])"#;
-const SUB_REGEX_PARAMETER:  &'static str = r#"/(.*)/(.*)/(.*)"#;
-const SUB_REGEX_BACKREF:    &'static str = r#"\\([0-9])"#;
+/// operations that accept a trailing `p`, `l`, or `n` print suffix
+const PRINT_SUFFIX_OPS: &'static str = "=djlmnpstuUz";
 
 // ^^^ Constants ^^^ }}}
 // *** Data Structures *** {{{
@@ -42,6 +46,8 @@ pub struct Command<'a, 'b> {// {{{
     pub address_final: usize,
     pub operation: char,
     pub parameters: &'a str,
+    /// trailing `p`, `l`, or `n`; prints current line after operation
+    pub print_suffix: Option<char>,
     pub operations: &'b Operations, // tagging along for the ride
 }// }}}
 pub struct Substitution {// {{{
@@ -49,6 +55,7 @@ pub struct Substitution {// {{{
     pub to_sub:   String,
     pub which: WhichMatch,
 }// }}}
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum WhichMatch {
     Number( usize ),
     Global,
@@ -63,29 +70,111 @@ pub fn parse_invocation( invoc_input: Vec<String>, state: &mut EditorState ) {//
         if invoc_input[indx] == "-s" || invoc_input[indx] == "-" {
             state.show_help = false;
             state.show_messages = false;
-            //println!( "help and messages turned off (except this one)" );
-            println!( "SILENT MODE ACTIVE" );
         } else if invoc_input[indx] == "-p" {
             if indx + 1 < invoc_input.len() {
                 indx += 1;
                 state.prompt = invoc_input[indx].clone();
-                println!( "prompt set to {}", &state.prompt );
+                state.show_prompt = true;
+                let message = format!( "prompt set to {}", &state.prompt );
+                print_msg( state, message );
             } else {
-                println!( "no prompt provided to \"-p\" flag" );
+                print_help( state, "no prompt provided to \"-p\" flag" );
             }
+        } else if invoc_input[indx] == "-u" {
+            if indx + 1 < invoc_input.len() {
+                indx += 1;
+                match invoc_input[indx].parse::<usize>() {
+                    Ok( kib ) => {
+                        state.u_set_memory_limit( kib * 1024 );
+                        print_msg( state, format!(
+                                "undo memory limit set to {} KiB", kib ));
+                    },
+                    Err( _ ) => print_help( state, format!(
+                            "invalid undo memory limit: {}",
+                            invoc_input[indx] )),
+                }
+            } else {
+                print_help( state, "no memory limit provided to \"-u\" flag" );
+            }
+        } else if invoc_input[indx] == "-b" {
+            // batch mode even when stdin is a terminal
+            state.batch = true;
+        } else if invoc_input[indx] == "-R" {
+            // no shell commands, no files outside current directory
+            state.buffer.restrict();
+        } else if invoc_input[indx] == "-E" {
+            state.regex_syntax = RegexSyntax::Extended;
+            print_msg( state, "using extended regular expressions" );
+        } else if invoc_input[indx] == "-j" {
+            if indx + 1 < invoc_input.len() {
+                indx += 1;
+                match parse_flush_policy( &invoc_input[indx] ) {
+                    Some( policy ) => {
+                        state.flush_policy = policy;
+                        print_msg( state, format!(
+                                "journal flush policy set to {:?}", policy ));
+                    },
+                    None => print_help( state, format!(
+                            "invalid journal flush policy: {}",
+                            invoc_input[indx] )),
+                }
+            } else {
+                print_help( state, "no flush policy provided to \"-j\" flag" );
+            }
+        } else if invoc_input[indx] == "-r" {
+            // sessions are listed by caller instead of editing
+            state.recovery_dir = Some( if indx + 1 < invoc_input.len() {
+                indx += 1;
+                invoc_input[indx].clone()
+            } else {
+                ".".to_string()
+            });
         } else if &invoc_input[indx][0..1] == "-" {
-            println!( "unrecognized flag: {}", invoc_input[indx] );
+            let message = format!( "unrecognized flag: {}", invoc_input[indx] );
+            print_help( state, message );
         } else {
             if state.source.len() == 0 {
                 state.source = invoc_input[indx].clone();
             } else {
-                println!( "only the first commnand or file name accepted" );
+                print_help( state, "only the first commnand or file name accepted" );
             }
         }
         indx += 1;
     }
 }//}}}
 //}}}}
+/// Describe temp files in dir holding sessions that can be recovered// {{{
+///
+/// Returns one line for each: the file it belongs to, how many lines it
+/// holds, how old it is, and the temp file name. A session is recovered by
+/// opening the file it belongs to
+pub fn recoverable_sessions( dir: &str ) -> Result<Vec<String>, RedError> {// {{{
+    let sessions = try!( list_sessions( dir ));
+    Ok( sessions.iter().map( |session| {
+        let temp_name = session.file_name().unwrap_or( OsStr::new( "" ))
+                               .to_string_lossy().into_owned();
+        let num_lines = replay( session ).map( |x| x.len().to_string() )
+                                         .unwrap_or( "?".to_string() );
+        let age = file_age( session ).map( |x| format_age( x ))
+                                     .unwrap_or( "?".to_string() );
+        format!( "{}\t{} lines, {} old\t({})",
+                 &temp_name[ TEMP_FILE_PREFIX.len() .. ], num_lines, age,
+                 temp_name )
+    }).collect() )
+}// }}}
+// }}}
+/// Parse journal flush policy given to `-j` flag// {{{
+///
+/// "command" flushes after every command, "quit" only when quitting with
+/// unsaved changes, and a number of seconds flushes at most that often
+fn parse_flush_policy( policy: &str ) -> Option<FlushPolicy> {// {{{
+    match policy {
+        "command" => Some( FlushPolicy::EveryCommand ),
+        "quit" => Some( FlushPolicy::OnQuit ),
+        _ => policy.parse::<u64>().ok().map( |x| FlushPolicy::Timed( x )),
+    }
+}// }}}
+// }}}
 /// Parses command-mode input {{{
 ///
 /// This is the public interface to the parse module
@@ -120,12 +209,15 @@ pub fn parse_command<'a,'b>( _cmd_input: &'a str, state: &mut EditorState,//{{{
             }
             let ( _address_initial, _address_final ) = try!(
                     get_address_range( addrs, state ) );
+            let ( _parameters, _print_suffix ) =
+                    split_print_suffix( _operation, _parameters );
 
             Ok( Command {
                     address_initial: _address_initial,
                     address_final: _address_final,
                     operation: _operation,
                     parameters: _parameters,
+                    print_suffix: _print_suffix,
                     operations: _operations,
                 }
             )
@@ -134,6 +226,39 @@ pub fn parse_command<'a,'b>( _cmd_input: &'a str, state: &mut EditorState,//{{{
 }// }}}
 //}}}
 
+/// Separate trailing print suffix from command parameters {{{
+///
+/// Only operations in PRINT_SUFFIX_OPS take a suffix; `'p` is a marker
+/// (as in `m'p`), not a suffix, and for substitutions the suffix must
+/// follow the closing delimiter, or the flags of a bare `s` (as in `sgp`)
+fn split_print_suffix( operation: char, parameters: &str )// {{{
+        -> ( &str, Option<char> ) {
+    if !PRINT_SUFFIX_OPS.contains( operation ) {
+        return ( parameters, None );
+    }
+    let suffix = match parameters.chars().last() {
+        Some( x ) if x == 'p' || x == 'l' || x == 'n' => x,
+        _ => return ( parameters, None ),
+    };
+    let rest = &parameters[ .. parameters.len() - 1 ];
+    if rest.ends_with( '\'' ) {
+        return ( parameters, None );
+    }
+    if operation == 's' && !is_substitution_repeat( rest ) {
+        let mut chars = rest.chars();
+        let closed = match chars.next() {
+            Some( delimiter ) =>
+                read_delimited( &mut chars, delimiter, "", true ).1 &&
+                read_delimited( &mut chars, delimiter, "", false ).1,
+            None => false,
+        };
+        if !closed {
+            return ( parameters, None );
+        }
+    }
+    ( rest.trim(), Some( suffix ))
+}// }}}
+//}}}
 /// Identify address range {{{
 ///
 /// What do we want to do if string ends in ',' or ';'?
@@ -151,10 +276,13 @@ fn get_address_range( address_string: &str, state: &mut EditorState )// {{{
         _ => parse_address_list( address_string ),
     };
 
-    let result_right = try!(parse_address_field( right, state )).unwrap();
+    // only a search finds no address, so anything missing is no match
+    let result_right = try!( try!( parse_address_field( right, state ))
+                             .ok_or( RedError::NoMatch ));
     let result_left = match left.len() {
         0 => result_right,
-        _ => try!(parse_address_field( left, state )).unwrap(),
+        _ => try!( try!( parse_address_field( left, state ))
+                   .ok_or( RedError::NoMatch )),
     };
     Ok( (result_left, result_right) )
 
@@ -307,18 +435,14 @@ pub fn parse_address_field( address: &str, state: &mut EditorState )// {{{
         let _to_match = re_fwdsearch.captures( address )
                .expect("parse_address_field: matched, now not matching...?")
                .at(1).expect("parse_address_field: missing expected capture");
-        if _to_match.trim().len() != 0 {
-            state.last_regex = _to_match.to_string();
-        }
-        Ok( state.buffer.find_match( &state.last_regex ))
+        let _to_match = try!( state.use_regex( _to_match ));
+        state.buffer.find_match( &_to_match )
     } else if re_revsearch.is_match( address ) {    // backward regex search?
         let _to_match = re_revsearch.captures( address )
                .expect("parse_address_field: matched, now not matching...?")
                .at(1).expect("parse_address_field: missing expected capture");
-        if _to_match.trim().len() != 0 {
-            state.last_regex = _to_match.to_string();
-        }
-        Ok( state.buffer.find_match_reverse( &state.last_regex ))
+        let _to_match = try!( state.use_regex( _to_match ));
+        state.buffer.find_match_reverse( &_to_match )
     } else {    // otherwise, replace any markers and calculate/parse
         let _address: &str = &re_marker.replace( address, |caps: &Captures| {
             state.buffer.get_marked_line( caps.at(1).unwrap_or("A").chars()
@@ -336,68 +460,187 @@ pub fn parse_address_field( address: &str, state: &mut EditorState )// {{{
 }// }}}
 // }}}
 /// Parse substitution parameter into separate parts// {{{
+///
+/// The first character is the delimiter, which may be any character other
+/// than space, newline, or backslash: `/pattern/replacement/flags`. The
+/// delimiter can appear in pattern or replacement when escaped with a
+/// backslash; other escapes are passed along untouched. As in ed, the
+/// closing delimiter may be left off at end of line
 pub fn parse_substitution_parameter( sub_parm: &str )// {{{
     -> Result<Substitution, RedError> {
-    let re_sub_parm: Regex = Regex::new( SUB_REGEX_PARAMETER ).unwrap();
-    match re_sub_parm.captures( sub_parm ) {
-        Some( cap ) => {
-            Ok( Substitution {
-                    to_match: match cap.at(1) {
-                        Some(x) => x.to_string(),
-                        None => return Err( RedError::ParameterSyntax{
-                            parameter: sub_parm.to_string() }),
-                    },
-                    to_sub: match cap.at(2) {
-                        Some(x) => x.to_string(),
-                        None => return Err( RedError::ParameterSyntax{
-                            parameter: sub_parm.to_string() }),
-                    },
-                    which: match cap.at(3) {
-                        Some(x) => {
-                            if x == "g" {
-                                WhichMatch::Global
-                            } else if x == "" {
-                                WhichMatch::Number(1)
-                            } else {
-                                WhichMatch::Number( try!( x.parse().map_err(|_|
-                                      RedError::ParameterSyntax{
-                                          parameter: sub_parm.to_string() })))
-                            }
-                        },
-                        None => return Err( RedError::ParameterSyntax{
-                            parameter: sub_parm.to_string() }),
-                    }
-            })
-        },
-        None => return Err( RedError::ParameterSyntax{
+    let mut chars = sub_parm.chars();
+    let delimiter = match chars.next() {
+        Some( x ) if !x.is_whitespace() && x != '\\' => x,
+        _ => return Err( RedError::ParameterSyntax{
             parameter: sub_parm.to_string() }),
+    };
+    let regex_delimiter = literal_delimiter( delimiter );
+    let ( to_match, _ ) = read_delimited( &mut chars, delimiter,
+                                          &regex_delimiter, true );
+    let sub_delimiter = match delimiter {
+        '&' => "\\&".to_string(),
+        _ => delimiter.to_string(),
+    };
+    let ( to_sub, _ ) = read_delimited( &mut chars, delimiter,
+                                        &sub_delimiter, false );
+    let flags: String = chars.collect();
+    Ok( Substitution {
+            to_match: to_match,
+            to_sub: to_sub,
+            which: try!( parse_substitution_flags( &flags )),
+    })
+}// }}}
+// }}}
+/// Return pattern matching escaped delimiter literally, BRE or ERE// {{{
+///
+/// A bracket expression holds the character literally in both syntaxes,
+/// where a backslash escape may instead turn it into an operator
+fn literal_delimiter( delimiter: char ) -> String {// {{{
+    match delimiter {
+        ']' => "[]]".to_string(),
+        '^' => "\\^".to_string(),
+        '\\' => "[\\]".to_string(),
+        x => format!( "[{}]", x ),
+    }
+}// }}}
+// }}}
+/// Return true if parameter of `s` asks to repeat last substitution// {{{
+///
+/// That is, it is empty or holds only a `g` or count
+pub fn is_substitution_repeat( sub_parm: &str ) -> bool {// {{{
+    sub_parm.chars().all( |x| x == 'g' || x.is_digit( 10 ))
+}// }}}
+// }}}
+/// Interpret flags following substitution: `g`, a count, or nothing// {{{
+pub fn parse_substitution_flags( flags: &str )// {{{
+        -> Result<WhichMatch, RedError> {
+    match flags.trim() {
+        "g" => Ok( WhichMatch::Global ),
+        "" => Ok( WhichMatch::Number(1) ),
+        x => Ok( WhichMatch::Number( try!( x.parse().map_err(|_|
+                RedError::ParameterSyntax{ parameter: flags.to_string() })))),
+    }
+}// }}}
+// }}}
+/// Collect characters up to unescaped delimiter, consuming the delimiter// {{{
+///
+/// An escaped delimiter is replaced by `escaped_delimiter`; all other
+/// escapes are kept as they are. With `brackets` set, as for a pattern, an
+/// escaped delimiter inside a bracket expression is just the bare character,
+/// since the bracket already holds it literally. Also returns whether the
+/// delimiter was found before the end of input
+fn read_delimited( chars: &mut Chars, delimiter: char,// {{{
+                   escaped_delimiter: &str, brackets: bool )
+        -> ( String, bool ) {
+    let mut result = String::new();
+    let mut in_bracket = false;
+    while let Some( ch ) = chars.next() {
+        if ch == delimiter {
+            return ( result, true );
+        } else if ch == '\\' {
+            match chars.next() {
+                Some( x ) if x == delimiter && in_bracket => result.push( x ),
+                Some( x ) if x == delimiter =>
+                    result.push_str( escaped_delimiter ),
+                Some( x ) => {
+                    result.push( '\\' );
+                    result.push( x );
+                },
+                None => result.push( '\\' ),
+            }
+        } else if ch == '[' && brackets && !in_bracket {
+            // a leading `^`, then a leading `]`, belong to the expression
+            result.push( ch );
+            in_bracket = true;
+            if chars.clone().next() == Some( '^' ) {
+                result.push( chars.next().unwrap_or( '^' ));
+            }
+            if chars.clone().next() == Some( ']' ) {
+                result.push( chars.next().unwrap_or( ']' ));
+            }
+        } else {
+            if ch == ']' {
+                in_bracket = false;
+            }
+            result.push( ch );
+        }
     }
+    ( result, false )
+}// }}}
+// }}}
+/// Expand shell command parameter for `!` operation// {{{
+///
+/// A leading `!` is replaced with the previous shell command, and any
+/// unescaped `%` is replaced with the current file name; `\%` is a
+/// literal `%`
+///
+/// Returns the expanded command and whether any expansion took place
+pub fn parse_shell_command( shell_parm: &str, state: &EditorState )// {{{
+        -> Result<(String, bool), RedError> {
+    let mut result: String = String::new();
+    let mut expanded: bool = false;
+    let mut _shell_parm: &str = shell_parm;
+    if _shell_parm.starts_with( '!' ) {
+        if state.last_shell_command.is_empty() {
+            return Err( RedError::ParameterSyntax{
+                parameter: "no previous shell command".to_string() });
+        }
+        result.push_str( &state.last_shell_command );
+        _shell_parm = &_shell_parm[1..];
+        expanded = true;
+    }
+    let mut chars = _shell_parm.chars().peekable();
+    loop {
+        match chars.next() {
+            Some( '\\' ) => {
+                if chars.peek() == Some( &'%' ) {
+                    result.push( '%' );
+                    chars.next();
+                } else {
+                    result.push( '\\' );
+                }
+            },
+            Some( '%' ) => {
+                match state.buffer.get_file_name() {
+                    Some( file_name ) => result.push_str(
+                        file_name.to_str().unwrap_or( "" ) ),
+                    None => return Err( RedError::ParameterSyntax{
+                        parameter: "no current file name".to_string() }),
+                }
+                expanded = true;
+            },
+            Some( ch ) => result.push( ch ),
+            None => break,
+        }
+    }
+    if result.trim().is_empty() {
+        return Err( RedError::ParameterSyntax{
+            parameter: shell_parm.to_string() });
+    }
+    Ok( ( result, expanded ) )
 }// }}}
 // }}}
 /// Replace regex capture references with the captures// {{{
+///
+/// As in POSIX, `&` is the whole match and `\1` through `\9` the groups
+/// (empty if the group did not take part in the match); a backslash makes
+/// any other character, including `&` and `\`, literal
 pub fn sub_captures( original: &str, captures: Captures )// {{{
         -> String {
     let mut result: String = String::new();
-    let re = Regex::new( SUB_REGEX_BACKREF ).unwrap();
-    let mut last_end: usize = 0;
-    let mut orig_captures = re.captures_iter( original );
-    for ( start, end ) in re.find_iter( original ) {
-        match orig_captures.next() {
-            Some(cap) => {
-                result += &original[ last_end .. start ];
-                // unwraps assured by setup - panic is reasonable here
-                // may want to replace with expect
-                match captures.at( cap.at(1).unwrap().parse().unwrap() ) {
-                    Some(x) => result += x,
-                    // or just leave as-is; already know cap.at(0) is not None
-                    None => result += cap.at(0).unwrap(),
-                };
+    let mut chars = original.chars();
+    while let Some( ch ) = chars.next() {
+        match ch {
+            '&' => result += captures.at(0).unwrap_or( "" ),
+            '\\' => match chars.next() {
+                Some( x ) if '1' <= x && x <= '9' => result +=
+                    captures.at( x as usize - '0' as usize ).unwrap_or( "" ),
+                Some( x ) => result.push( x ),
+                None => result.push( '\\' ),
             },
-            None => break,
+            _ => result.push( ch ),
         }
-        last_end = end;
     }
-    result += &original[ last_end .. ];
     result
 }// }}}
 // }}}
@@ -408,7 +651,8 @@ pub fn sub_captures( original: &str, captures: Captures )// {{{
 /// of times as part of a regular expression used to specify an address
 /// range that matches;
 /// What this function does is simply locates the first alphabetic character
-/// that is not wrapped in either /.../ or ?...?
+/// (or `!`, the shell operation, or `=`, the line number query) that is not
+/// wrapped in either /.../ or ?...?
 ///
 /// Trims white space on the left as well - does not count these characters
 ///
@@ -422,7 +666,7 @@ fn get_opchar_index( _cmd_input: &str ) -> Result<(usize, char), RedError> {
             Some( x ) => {
                 if _cmd_input.is_char_boundary( current_indx ) {
                     match x {
-                        b'a'...b'z' | b'A'...b'Z' => {
+                        b'a'...b'z' | b'A'...b'Z' | b'!' | b'=' => {
                             if !is_in_addr( _cmd_input.trim(), current_indx ) {
                                 return Ok( (current_indx, x as char ) );
                             }
@@ -533,7 +777,11 @@ pub fn parse_global_op<'a>( g_op: &'a str )// {{{
 // ^^^ Functions ^^^ }}}
 #[cfg(test)]
 mod tests {
-    use super::{get_opchar_index, is_in_regex, parse_address_field, parse_address_list, get_address_range, is_address_separator};
+    use super::{get_opchar_index, is_in_regex, parse_address_field, parse_address_list, get_address_range, is_address_separator, parse_shell_command,
+                split_print_suffix, parse_substitution_parameter, WhichMatch,
+                sub_captures};
+    use ::regex::Regex;
+    use posix::{ translate, RegexSyntax };
     use buf::*;
     use ::EditorState;
 
@@ -655,6 +903,97 @@ mod tests {
                 (37, 's') );
     }
 
+    /// Shell operation character
+    #[test]
+    fn get_opchar_index_test_10() {
+        let _in: &str = "1,3!sort -r";
+        assert_eq!( get_opchar_index( _in ).unwrap_or( (9999, '\0') ),
+                (3, '!') );
+    }
+
+    /// Line number query following regex address
+    #[test]
+    fn get_opchar_index_test_11() {
+        let _in: &str = "/a=b/=";
+        assert_eq!( get_opchar_index( _in ).unwrap_or( (9999, '\0') ),
+                (5, '=') );
+    }
+
+    /// Print suffixes are split off only where they can't be parameters
+    #[test]
+    fn split_print_suffix_test_1() {
+        assert_eq!( split_print_suffix( 'd', "p" ), ( "", Some( 'p' )));
+        assert_eq!( split_print_suffix( 's', "/a/b/gn" ),
+                    ( "/a/b/g", Some( 'n' )));
+        assert_eq!( split_print_suffix( 's', "/a/n" ), ( "/a/n", None ));
+        assert_eq!( split_print_suffix( 's', "p" ), ( "", Some( 'p' )));
+        assert_eq!( split_print_suffix( 's', "gp" ), ( "g", Some( 'p' )));
+        assert_eq!( split_print_suffix( 's', "3l" ), ( "3", Some( 'l' )));
+        assert_eq!( split_print_suffix( 's', r"/a\/n" ), ( r"/a\/n", None ));
+        assert_eq!( split_print_suffix( 'm', "'p" ), ( "'p", None ));
+        assert_eq!( split_print_suffix( 't', "$l" ), ( "$", Some( 'l' )));
+        assert_eq!( split_print_suffix( 'z', "5n" ), ( "5", Some( 'n' )));
+        assert_eq!( split_print_suffix( 'k', "p" ), ( "p", None ));
+        assert_eq!( split_print_suffix( 'g', "/x/p" ), ( "/x/p", None ));
+    }
+
+    /// Substitution with other delimiters and escaped delimiters
+    #[test]
+    fn parse_substitution_parameter_test_1() {
+        let sub = parse_substitution_parameter( "|/usr|/opt|g" ).unwrap();
+        assert_eq!( ( sub.to_match.as_str(), sub.to_sub.as_str() ),
+                    ( "/usr", "/opt" ));
+        match sub.which {
+            WhichMatch::Global => {},
+            _ => panic!( "expected global substitution" ),
+        }
+        let sub = parse_substitution_parameter( r"/a\/b/c\/d\1/3" ).unwrap();
+        assert_eq!( ( sub.to_match.as_str(), sub.to_sub.as_str() ),
+                    ( "a[/]b", r"c/d\1" ));
+        match sub.which {
+            WhichMatch::Number( 3 ) => {},
+            _ => panic!( "expected third match" ),
+        }
+        let sub = parse_substitution_parameter( r".a\.b.c" ).unwrap();
+        assert_eq!( ( sub.to_match.as_str(), sub.to_sub.as_str() ),
+                    ( "a[.]b", "c" ));
+        let sub = parse_substitution_parameter( "/a" ).unwrap();
+        assert_eq!( ( sub.to_match.as_str(), sub.to_sub.as_str() ),
+                    ( "a", "" ));
+        assert!( parse_substitution_parameter( " a b " ).is_err() );
+        assert!( parse_substitution_parameter( "/a/b/x" ).is_err() );
+    }
+
+    /// Escaped delimiter matches itself whether or not it is an operator
+    #[test]
+    fn parse_substitution_parameter_test_2() {
+        let cases = [ ( r"|a\|b|X|", "a|b c", "X c" ),
+                      ( r"+b\+c+Y+", "bbc b+c", "bbc Y" ),
+                      ( r"?a\?b?Z?", "ab a?b", "ab Z" ),
+                      ( r"]a\]b]W]", "ab a]b", "ab W" ),
+                      ( r"^a\^b^V^", "ab a^b", "ab V" ),
+                      ( r"/[\/]/X/", "a/b", "aXb" ),
+                      ( r"/[^\/]/X/", "/b", "/X" ) ];
+        for syntax in &[ RegexSyntax::Basic, RegexSyntax::Extended ] {
+            for &( parameter, line, result ) in &cases {
+                let sub = parse_substitution_parameter( parameter ).unwrap();
+                let re = Regex::new( &translate( &sub.to_match, *syntax ))
+                        .unwrap();
+                assert_eq!( re.replace( line, sub.to_sub.as_str() ), result,
+                            "{} in {:?}", parameter, syntax );
+            }
+        }
+    }
+
+    /// Replacement with whole match, groups, and escapes
+    #[test]
+    fn sub_captures_test_1() {
+        let re = Regex::new( "(a+)(x)?(b)" ).unwrap();
+        let captures = re.captures( "caab" ).unwrap();
+        assert_eq!( sub_captures( r"[&]\3\1\2\&\\\q", captures ),
+                    r"[aab]baa&\q" );
+    }
+
     #[test]
     fn is_in_regex_test_1() {
         let haystack = "This is a / abc /string to search";
@@ -790,6 +1129,26 @@ mod tests {
 
     }
     #[test]
+    fn parse_shell_command_test_1() {
+        let test_num: u8 = 16;
+        let mut state = open_command_buffer_test( test_num );
+        let ( cmd, expanded ) = parse_shell_command( "wc -l %", &state )
+            .unwrap();
+        assert_eq!( cmd, "wc -l red_filetest.cmd16" );
+        assert!( expanded );
+        let ( cmd, expanded ) = parse_shell_command( "echo 50\\%", &state )
+            .unwrap();
+        assert_eq!( cmd, "echo 50%" );
+        assert!( !expanded );
+        assert!( parse_shell_command( "!", &state ).is_err() );
+        state.last_shell_command = "ls -a".to_string();
+        let ( cmd, expanded ) = parse_shell_command( "! -l", &state )
+            .unwrap();
+        assert_eq!( cmd, "ls -a -l" );
+        assert!( expanded );
+        close_command_buffer_test( &mut state );
+    }
+    #[test]
     fn parse_address_list_test_1() {
         // set contstants
         let address_string: &str = "1, 3";
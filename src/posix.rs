@@ -0,0 +1,207 @@
+/*
+ * File   : posix.rs
+ * Purpose: translate POSIX regular expressions into regex crate syntax
+ * Program: red
+ * About  : command-line text editor
+ * Authors: Tommy Lincoln <pajamapants3000@gmail.com>
+ * License: MIT; See LICENSE!
+ * Notes  : Notes on successful compilation
+ * Created: 10/17/2026
+ */
+
+//! POSIX regular expression translation
+//!
+//! Patterns are typed as in ed and sed: basic regular expressions (BRE) by
+//! default, or extended regular expressions (ERE) when selected with the
+//! `-E` flag. Either is rewritten into the syntax of the regex crate before
+//! being compiled.
+//!
+//! In a BRE, `\(`, `\)`, `\{`, `\}` are grouping and intervals while the
+//! bare characters are literal, as are `+`, `?` and `|` (the GNU `\+`, `\?`
+//! and `\|` are accepted); `*` is literal at the start of an expression,
+//! `^` only anchors at the start and `$` only at the end. In both syntaxes
+//! `\<` and `\>` match at word boundaries and bracket expressions may hold
+//! character classes such as `[:alpha:]`.
+// *** Bring in to namespace *** {{{
+use ::regex::quote;
+// ^^^ Bring in to namespace ^^^ }}}
+
+// *** Data Structures *** {{{
+/// Flavor of regular expression typed by the user// {{{
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RegexSyntax {// {{{
+    Basic,
+    Extended,
+}// }}}
+// }}}
+// ^^^ Data Structures ^^^ }}}
+
+// *** Functions *** {{{
+/// Rewrite POSIX regular expression in regex crate syntax// {{{
+pub fn translate( pattern: &str, syntax: RegexSyntax ) -> String {// {{{
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut result = String::with_capacity( pattern.len() );
+    // at start of expression or group, where `*` is literal, `^` anchors
+    let mut at_start = true;
+    let mut indx: usize = 0;
+    while indx < chars.len() {
+        let ch = chars[indx];
+        indx += 1;
+        let mut starts_expr = false;
+        match ch {
+            '[' => indx = translate_bracket( &chars, indx, &mut result ),
+            '\\' if indx < chars.len() => {
+                let escaped = chars[indx];
+                indx += 1;
+                match ( syntax, escaped ) {
+                    ( _, '<' ) | ( _, '>' ) => result.push_str( "\\b" ),
+                    ( RegexSyntax::Basic, '(' ) |
+                    ( RegexSyntax::Basic, '|' ) => {
+                        result.push( escaped );
+                        starts_expr = true;
+                    },
+                    ( RegexSyntax::Basic, ')' ) | ( RegexSyntax::Basic, '{' ) |
+                    ( RegexSyntax::Basic, '}' ) | ( RegexSyntax::Basic, '+' ) |
+                    ( RegexSyntax::Basic, '?' ) => result.push( escaped ),
+                    // classes like \w, and back-references
+                    ( _, x ) if x.is_alphanumeric() => {
+                        result.push( '\\' );
+                        result.push( x );
+                    },
+                    ( _, x ) => result.push_str( &quote( &x.to_string() )),
+                }
+            },
+            '\\' => result.push_str( "\\\\" ),
+            '*' if at_start => result.push_str( "\\*" ),
+            '^' if at_start => {
+                result.push( '^' );
+                starts_expr = true;
+            },
+            '^' if syntax == RegexSyntax::Basic => result.push_str( "\\^" ),
+            '$' if syntax == RegexSyntax::Basic &&
+                    !is_bre_end( &chars, indx ) => result.push_str( "\\$" ),
+            '(' | ')' | '{' | '}' | '|' | '+' | '?'
+                    if syntax == RegexSyntax::Basic => {
+                result.push( '\\' );
+                result.push( ch );
+            },
+            '(' | '|' => {
+                result.push( ch );
+                starts_expr = true;
+            },
+            _ => result.push( ch ),
+        }
+        at_start = starts_expr;
+    }
+    result
+}// }}}
+// }}}
+/// Return true if indx is at end of BRE or of a group or alternative// {{{
+fn is_bre_end( chars: &[char], indx: usize ) -> bool {// {{{
+    indx == chars.len() ||
+        ( chars[indx] == '\\' && indx + 1 < chars.len() &&
+          ( chars[ indx + 1 ] == ')' || chars[ indx + 1 ] == '|' ))
+}// }}}
+// }}}
+/// Translate bracket expression starting just after its `[`// {{{
+///
+/// Returns index just past the closing `]`. Backslash is literal inside a
+/// POSIX bracket expression, as is `]` when it comes first
+fn translate_bracket( chars: &[char], mut indx: usize,// {{{
+                      result: &mut String ) -> usize {
+    result.push( '[' );
+    if indx < chars.len() && chars[indx] == '^' {
+        result.push( '^' );
+        indx += 1;
+    }
+    if indx < chars.len() && chars[indx] == ']' {
+        result.push_str( "\\]" );
+        indx += 1;
+    }
+    while indx < chars.len() {
+        let ch = chars[indx];
+        indx += 1;
+        match ch {
+            ']' => {
+                result.push( ']' );
+                return indx;
+            },
+            '[' if indx < chars.len() && chars[indx] == ':' => {
+                // character class; copied whole, e.g. [:alpha:]
+                result.push( '[' );
+                while indx < chars.len() {
+                    result.push( chars[indx] );
+                    indx += 1;
+                    if chars[ indx - 1 ] == ']' && chars[ indx - 2 ] == ':' {
+                        break;
+                    }
+                }
+            },
+            '[' if indx < chars.len() &&
+                    ( chars[indx] == '=' || chars[indx] == '.' ) => {
+                // equivalence class or collating symbol; single characters
+                // stand for themselves
+                let kind = chars[indx];
+                indx += 1;
+                while indx < chars.len() && !( chars[indx] == kind &&
+                        indx + 1 < chars.len() && chars[ indx + 1 ] == ']' ) {
+                    if chars[indx] == '-' {
+                        result.push( '\\' );
+                    }
+                    result.push_str( &quote( &chars[indx].to_string() ));
+                    indx += 1;
+                }
+                indx += 2;
+            },
+            '[' | '\\' | '&' | '~' => {
+                result.push( '\\' );
+                result.push( ch );
+            },
+            _ => result.push( ch ),
+        }
+    }
+    indx
+}// }}}
+// }}}
+// ^^^ Functions ^^^ }}}
+
+#[cfg(test)]
+mod tests {// {{{
+    use super::*;
+
+    /// Basic regular expressions// {{{
+    #[test]
+    fn translate_test_1() {// {{{
+        let bre = | x: &str | translate( x, RegexSyntax::Basic );
+        assert_eq!( bre( r"\(foo\)\{2\}" ), "(foo){2}" );
+        assert_eq!( bre( "a+b?(c)|{d}" ), r"a\+b\?\(c\)\|\{d\}" );
+        assert_eq!( bre( r"a\+b\?" ), "a+b?" );
+        assert_eq!( bre( r"\<word\>" ), r"\bword\b" );
+        assert_eq!( bre( "*a*" ), r"\*a*" );
+        assert_eq!( bre( r"^*a^b$c$" ), r"^\*a\^b\$c$" );
+        assert_eq!( bre( r"\(^a$\)" ), "(^a$)" );
+        assert_eq!( bre( r"a\.b\/c\w" ), r"a\.b/c\w" );
+    }// }}}
+    // }}}
+    /// Bracket expressions// {{{
+    #[test]
+    fn translate_test_2() {// {{{
+        let bre = | x: &str | translate( x, RegexSyntax::Basic );
+        assert_eq!( bre( "[[:alpha:]_]" ), "[[:alpha:]_]" );
+        assert_eq!( bre( r"[]a\]" ), r"[\]a\\]" );
+        assert_eq!( bre( "[^]x]" ), r"[^\]x]" );
+        assert_eq!( bre( "[[.-.][=a=]]" ), r"[\-a]" );
+        assert_eq!( bre( "[a[]+" ), r"[a\[]\+" );
+    }// }}}
+    // }}}
+    /// Extended regular expressions// {{{
+    #[test]
+    fn translate_test_3() {// {{{
+        let ere = | x: &str | translate( x, RegexSyntax::Extended );
+        assert_eq!( ere( "(foo){2}|b+c?" ), "(foo){2}|b+c?" );
+        assert_eq!( ere( r"\(a\)" ), r"\(a\)" );
+        assert_eq!( ere( r"\<[[:digit:]]+\>" ), r"\b[[:digit:]]+\b" );
+        assert_eq!( ere( "(*a)" ), r"(\*a)" );
+    }// }}}
+    // }}}
+}// }}}
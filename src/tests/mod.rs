@@ -1,34 +0,0 @@
-/*
-    * File   : test/mod.rs
-    * Purpose: test module
-    * Program: red
-    * About  : command-line text editor
-    * Authors: Tommy Lincoln <pajamapants3000@gmail.com>
-    * License: MIT; See LICENSE!
-    * Notes  : Notes on successful compilation
-    * Created: 10/26/2016
-    */
-
-// *** Bring in to namespace *** {{{
-//use super::*;
-//use parse::*;
-//use error::*;
-
-// ^^^ Bring in to namespace ^^^ }}}
-// *** Attributes *** {{{
-// ^^^ Attributes ^^^ }}}
-// *** Constants *** {{{
-// ^^^ Constants ^^^ }}}
-// *** Data Structures *** {{{
-// ^^^ Data Structures ^^^ }}}
-// *** Functions *** {{{
-
-
-#[cfg(not(test))]
-fn main() {
-       println!(
-           "If you are reading this, the tests were neither compiled nor run!");
-}
-
-// ^^^ Functions ^^^ }}}
-
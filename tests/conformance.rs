@@ -0,0 +1,160 @@
+/*
+ * File   : tests/conformance.rs
+ * Purpose: run ed scripts and compare results to expected files
+ * Program: red
+ * About  : command-line text editor
+ * Authors: Tommy Lincoln <pajamapants3000@gmail.com>
+ * License: MIT; See LICENSE!
+ * Notes  : record expectations with `RED_RECORD=1 cargo test --test conformance`
+ * Created: 10/17/2026
+ */
+
+//! Golden-file conformance tests
+//!
+//! Each `name.ed` in the corpus directory is a script run in batch mode
+//! against a copy of `name.txt`, or against a new file if there is none.
+//! Scripts named `interactive_*` are run as if typed at a terminal
+//! instead, so prompts are shown and errors don't end the session.
+//! The final buffer, everything printed, and the exit status are compared
+//! to `name.buffer`, `name.output`, and `name.status`. With `RED_RECORD`
+//! set, those files are written from the results instead.
+// *** Bring in to namespace *** {{{
+extern crate red;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use red::{Editor, MemoryTerminal};
+// ^^^ Bring in to namespace ^^^ }}}
+
+// *** Constants *** {{{
+const CORPUS_DIR: &'static str = "test";
+const RECORD_VAR: &'static str = "RED_RECORD";
+/// scripts with names starting so are not run in batch mode
+const INTERACTIVE_PREFIX: &'static str = "interactive_";
+// ^^^ Constants ^^^ }}}
+
+// *** Data Structures *** {{{
+/// What running a script produced// {{{
+struct Outcome {
+    buffer: String,
+    output: String,
+    status: String,
+}
+// }}}
+// ^^^ Data Structures ^^^ }}}
+
+// *** Functions *** {{{
+/// Return content of file, or None if it doesn't exist// {{{
+fn read_file( path: &Path ) -> Option<String> {// {{{
+    let mut content = String::new();
+    match File::open( path ) {
+        Ok( mut file ) => {
+            file.read_to_string( &mut content )
+                .expect( "read_file: unable to read file" );
+            Some( content )
+        },
+        Err( _ ) => None,
+    }
+}// }}}
+// }}}
+/// Write content to file, replacing it// {{{
+fn write_file( path: &Path, content: &str ) {// {{{
+    File::create( path ).and_then( |mut file| file.write_all(
+            content.as_bytes() )).expect( "write_file: unable to write file" );
+}// }}}
+// }}}
+/// Run script in work_dir, on copy of its input file// {{{
+fn run_script( script: &Path, work_dir: &Path ) -> Outcome {// {{{
+    let name = script.file_stem().expect( "run_script: no file name" )
+                     .to_string_lossy().into_owned();
+    let work_file = work_dir.join( name.clone() + ".txt" );
+    if let Some( input ) = read_file( &script.with_extension( "txt" )) {
+        write_file( &work_file, &input );
+    }
+    let commands = read_file( script ).expect( "run_script: no script" );
+    let terminal = MemoryTerminal::new( &commands );
+    let args = vec!( "red".to_string(),
+                     work_file.to_string_lossy().into_owned() );
+    let batch = !name.starts_with( INTERACTIVE_PREFIX );
+    let mut editor = Editor::from_invocation( args, batch )
+        .expect( "run_script: unable to start editor" );
+    editor.set_terminal( Box::new( terminal.clone() ));
+    let status = match editor.load_source() {
+        Ok( () ) => editor.session(),
+        Err( e ) => match editor.fail( e ) {
+            Some( status ) => status,
+            None => editor.session(),
+        },
+    };
+    let mut buffer = String::new();
+    for line in editor.lines() {
+        buffer.push_str( &line );
+        buffer.push( '\n' );
+    }
+    editor.close();
+    Outcome{ buffer: buffer, output: terminal.take_output(),
+             status: format!( "{}\n", status ) }
+}// }}}
+// }}}
+/// Compare result to expectation file, or record it; describe mismatch// {{{
+fn check( expected_path: &Path, actual: &str, record: bool )// {{{
+        -> Option<String> {
+    if record {
+        write_file( expected_path, actual );
+        return None;
+    }
+    match read_file( expected_path ) {
+        Some( ref expected ) if expected == actual => None,
+        Some( expected ) => Some( format!(
+                "{}:\n--- expected\n{}--- actual\n{}",
+                expected_path.display(), expected, actual )),
+        None => Some( format!( "{}: missing; record with {}=1",
+                               expected_path.display(), RECORD_VAR )),
+    }
+}// }}}
+// }}}
+/// Return scripts in corpus, in name order// {{{
+fn corpus_scripts() -> Vec<PathBuf> {// {{{
+    let corpus = Path::new( env!( "CARGO_MANIFEST_DIR" )).join( CORPUS_DIR );
+    let mut scripts: Vec<PathBuf> = fs::read_dir( &corpus )
+        .expect( "corpus_scripts: unable to read corpus directory" )
+        .map( |entry| entry.expect( "corpus_scripts: bad entry" ).path() )
+        .filter( |path| path.extension().map_or( false, |x| x == "ed" ))
+        .collect();
+    scripts.sort();
+    scripts
+}// }}}
+// }}}
+// ^^^ Functions ^^^ }}}
+
+#[test]
+fn conformance() {// {{{
+    let record = env::var_os( RECORD_VAR ).is_some();
+    let scripts = corpus_scripts();
+    assert!( !scripts.is_empty(), "no scripts in corpus" );
+    let mut failures: Vec<String> = Vec::new();
+    for script in &scripts {
+        let work_dir = env::temp_dir().join( format!( "red-conformance-{}",
+                                                      process::id() ));
+        fs::create_dir_all( &work_dir )
+            .expect( "conformance: unable to create work directory" );
+        let outcome = run_script( script, &work_dir );
+        fs::remove_dir_all( &work_dir )
+            .expect( "conformance: unable to remove work directory" );
+        let results = [ ( "buffer", &outcome.buffer ),
+                        ( "output", &outcome.output ),
+                        ( "status", &outcome.status ) ];
+        for &( extension, actual ) in &results {
+            if let Some( failure ) = check( &script.with_extension( extension ),
+                                            actual, record ) {
+                failures.push( failure );
+            }
+        }
+    }
+    assert!( failures.is_empty(), "{} of {} scripts differ:\n\n{}",
+             failures.len(), scripts.len(), failures.join( "\n" ));
+}// }}}